@@ -0,0 +1,83 @@
+// Compares reading every branch's details against the two-pass listing, which sorts
+// on commit time alone and loads details only for the branches that get shown.
+// Run with `cargo bench`.
+use std::env;
+use std::fs;
+use std::process;
+use std::time::{Duration, Instant};
+
+use git2::{Repository, Signature, Time};
+
+use git_checkout_recent::git::{extract_local_branches, load_details, sort_records, SortKey};
+
+const BRANCHES: usize = 3000;
+// Roughly what fits on screen, as with the picker's first batch
+const SHOWN: usize = 50;
+const RUNS: u32 = 5;
+
+fn build_repo() -> (Repository, std::path::PathBuf) {
+    let dir = env::temp_dir().join(format!("git-checkout-recent-bench-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let repo = Repository::init(&dir).unwrap();
+    let tree_id = repo.treebuilder(None).unwrap().write().unwrap();
+    {
+        let tree = repo.find_tree(tree_id).unwrap();
+        let mut parent = None;
+        for i in 0..BRANCHES {
+            let time = Time::new(1_600_000_000 + i as i64 * 60, 0);
+            let signature = Signature::new("Bench", "bench@example.com", &time).unwrap();
+            let parents: Vec<_> = parent.iter().collect();
+            let message = format!("Commit {i}\n\nBody of commit {i}");
+            let oid = repo
+                .commit(None, &signature, &signature, &message, &tree, &parents)
+                .unwrap();
+            let commit = repo.find_commit(oid).unwrap();
+            repo.branch(&format!("branch-{i:05}"), &commit, false)
+                .unwrap();
+            parent = Some(commit);
+        }
+    }
+    (repo, dir)
+}
+
+fn time<F: FnMut() -> usize>(name: &str, mut f: F) -> Duration {
+    let start = Instant::now();
+    let mut loaded = 0;
+    for _ in 0..RUNS {
+        loaded = f();
+    }
+    let elapsed = start.elapsed() / RUNS;
+    println!("{name:<10} {elapsed:>12.2?} per run, details loaded for {loaded} branches");
+    elapsed
+}
+
+fn main() {
+    let (repo, dir) = build_repo();
+    println!("{BRANCHES} branches, {SHOWN} shown");
+
+    let eager = time("eager", || {
+        let mut records = extract_local_branches(&repo);
+        for record in records.iter_mut() {
+            load_details(&repo, record);
+        }
+        sort_records(&mut records, SortKey::Recency);
+        records.truncate(SHOWN);
+        BRANCHES
+    });
+    let two_pass = time("two-pass", || {
+        let mut records = extract_local_branches(&repo);
+        sort_records(&mut records, SortKey::Recency);
+        records.truncate(SHOWN);
+        for record in records.iter_mut() {
+            load_details(&repo, record);
+        }
+        records.len()
+    });
+    println!(
+        "two-pass takes {:.0}% of the eager time",
+        100.0 * two_pass.as_secs_f64() / eager.as_secs_f64()
+    );
+
+    drop(repo);
+    let _ = fs::remove_dir_all(dir);
+}
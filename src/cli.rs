@@ -0,0 +1,356 @@
+use super::git::{DateFormat, SortKey};
+
+// How many branches the output for scripts lists without --limit
+const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Text,
+    Json,
+}
+
+pub struct Options {
+    pub contains: Option<String>,
+    pub align: bool,
+    pub pull: bool,
+    pub wrap: bool,
+    pub checkout: Option<String>,
+    pub preview_command: Option<String>,
+    pub prompt: bool,
+    pub batch_size: usize,
+    pub sort_key: SortKey,
+    pub peek: bool,
+    pub show_remote: bool,
+    pub previous: bool,
+    pub sha_length: Option<usize>,
+    pub stat: bool,
+    pub stdin: bool,
+    pub current_first: bool,
+    pub json_lines: bool,
+    pub json: bool,
+    pub autostash: bool,
+    pub hide_merged: bool,
+    pub detach: bool,
+    pub glob: Option<String>,
+    pub autostash_pop: bool,
+    pub undo: bool,
+    pub committer: Option<String>,
+    pub children_of: Option<String>,
+    pub group_by_age: bool,
+    pub max_width: Option<u16>,
+    pub unpushed: bool,
+    pub include_no_upstream: bool,
+    pub author_max_width: Option<usize>,
+    pub author_first_name: bool,
+    pub merged_here: bool,
+    pub full_refs: bool,
+    pub update_submodules: bool,
+    pub export: Option<String>,
+    pub export_format: ExportFormat,
+    pub ref_prefix: Option<String>,
+    pub exec: Option<String>,
+    pub idle_timeout: Option<u64>,
+    pub date_format: DateFormat,
+    pub force_unclean: bool,
+    pub ff_if_behind: bool,
+    pub dedup_tips: bool,
+    pub explain: bool,
+    pub read_only: bool,
+    pub checkout_new: Option<String>,
+    pub stashes: bool,
+    pub confirm: bool,
+    pub hyperlinks: bool,
+    pub stale_days: Option<i64>,
+    pub profile: bool,
+    pub keep_open_on_current: bool,
+    pub notify: bool,
+    pub oldest_first: bool,
+    // Most branches to list, after sorting, as given with --limit; see `limit()`
+    pub limit: Option<usize>,
+    pub remotes: bool,
+    pub last: bool,
+    // End records in list output with NUL instead of a newline, for xargs -0
+    pub null: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            contains: None,
+            align: false,
+            pull: false,
+            wrap: false,
+            checkout: None,
+            preview_command: None,
+            prompt: false,
+            batch_size: 20,
+            sort_key: SortKey::Recency,
+            peek: false,
+            show_remote: false,
+            previous: false,
+            sha_length: None,
+            stat: false,
+            stdin: false,
+            current_first: false,
+            json_lines: false,
+            json: false,
+            autostash: false,
+            hide_merged: false,
+            detach: false,
+            glob: None,
+            autostash_pop: false,
+            undo: false,
+            committer: None,
+            children_of: None,
+            group_by_age: false,
+            max_width: None,
+            unpushed: false,
+            include_no_upstream: false,
+            author_max_width: None,
+            author_first_name: false,
+            merged_here: false,
+            full_refs: false,
+            update_submodules: false,
+            export: None,
+            export_format: ExportFormat::Text,
+            ref_prefix: None,
+            exec: None,
+            idle_timeout: None,
+            date_format: DateFormat::Relative,
+            force_unclean: false,
+            ff_if_behind: false,
+            dedup_tips: false,
+            explain: false,
+            read_only: false,
+            checkout_new: None,
+            stashes: false,
+            confirm: false,
+            hyperlinks: false,
+            stale_days: None,
+            profile: false,
+            keep_open_on_current: false,
+            notify: false,
+            oldest_first: false,
+            limit: None,
+            remotes: false,
+            last: false,
+            null: false,
+        }
+    }
+}
+
+impl Options {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut options = Options::default();
+        while let Some(arg) = args.next() {
+            // Accept both "--flag value" and "--flag=value"
+            let (flag, inline_value) = match arg.find('=') {
+                Some(i) if arg.starts_with("--") => {
+                    (arg[..i].to_string(), Some(arg[i + 1..].to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || match inline_value.clone() {
+                Some(v) => Ok(v),
+                None => args
+                    .next()
+                    .ok_or_else(|| format!("missing value for '{}'", flag)),
+            };
+            match flag.as_str() {
+                "--contains" => options.contains = Some(value()?),
+                "--unpushed" => options.unpushed = true,
+                "--include-no-upstream" => options.include_no_upstream = true,
+                "--committer" => options.committer = Some(value()?),
+                "--children-of" => options.children_of = Some(value()?),
+                "--prefix" => options.ref_prefix = Some(value()?),
+                "--glob" => options.glob = Some(value()?),
+                "--align" => options.align = true,
+                "--checkout-and-pull" | "--pull" => options.pull = true,
+                "--ff-if-behind" => options.ff_if_behind = true,
+                "--wrap" => options.wrap = true,
+                "--checkout" => options.checkout = Some(value()?),
+                "--checkout-new" => options.checkout_new = Some(value()?),
+                "--preview-command" => options.preview_command = Some(value()?),
+                "--prompt" => options.prompt = true,
+                "--sort-by-size" => options.sort_key = SortKey::Size,
+                "--frecency" => options.sort_key = SortKey::Frecency,
+                "--sort-by-created" => options.sort_key = SortKey::Created,
+                "--oldest-first" => options.oldest_first = true,
+                "--peek" => options.peek = true,
+                "--confirm" => options.confirm = true,
+                "--force-unclean" => options.force_unclean = true,
+                "--read-only" => options.read_only = true,
+                "--update-submodules" => options.update_submodules = true,
+                "--exec" => options.exec = Some(value()?),
+                "--show-remote" => options.show_remote = true,
+                "--remote" | "--all" => options.remotes = true,
+                "--full-refs" => options.full_refs = true,
+                "--hyperlinks" => options.hyperlinks = true,
+                "--stat" => options.stat = true,
+                "--profile" => options.profile = true,
+                "--keep-open-on-current" => options.keep_open_on_current = true,
+                "--notify" => options.notify = true,
+                "--null" | "-z" => options.null = true,
+                "--group-by-age" => options.group_by_age = true,
+                "--dedup-tips" => options.dedup_tips = true,
+                "--stashes" => options.stashes = true,
+                "--stdin" => options.stdin = true,
+                "--current-first" => options.current_first = true,
+                "--json-lines" => options.json_lines = true,
+                "--json" => options.json = true,
+                "--autostash" => options.autostash = true,
+                "--hide-merged" => options.hide_merged = true,
+                "--detach" => options.detach = true,
+                "--autostash-pop" => {
+                    options.autostash = true;
+                    options.autostash_pop = true;
+                }
+                "--explain" => options.explain = true,
+                "--merged-here" => options.merged_here = true,
+                "--undo" => options.undo = true,
+                "--previous" | "-" => options.previous = true,
+                "--last" => options.last = true,
+                "--export" => options.export = Some(value()?),
+                "--date" => {
+                    let v = value()?;
+                    options.date_format = match v.as_str() {
+                        "relative" => DateFormat::Relative,
+                        "iso" => DateFormat::Iso,
+                        "both" => DateFormat::Both,
+                        _ => return Err(format!("invalid value '{}' for '{}'", v, flag)),
+                    };
+                }
+                "--format" => {
+                    let v = value()?;
+                    options.export_format = match v.as_str() {
+                        "text" => ExportFormat::Text,
+                        "json" => ExportFormat::Json,
+                        _ => return Err(format!("invalid value '{}' for '{}'", v, flag)),
+                    };
+                }
+                "--limit" => {
+                    let v = value()?;
+                    let limit: usize = v
+                        .parse()
+                        .map_err(|_| format!("invalid value '{}' for '{}'", v, flag))?;
+                    options.limit = Some(limit);
+                }
+                "--batch-size" => {
+                    let v = value()?;
+                    options.batch_size = v
+                        .parse()
+                        .map_err(|_| format!("invalid value '{}' for '{}'", v, flag))?;
+                }
+                "--sha-length" => {
+                    let v = value()?;
+                    let length = v
+                        .parse()
+                        .map_err(|_| format!("invalid value '{}' for '{}'", v, flag))?;
+                    options.sha_length = Some(length);
+                }
+                "--author-first-name" => options.author_first_name = true,
+                "--author-max-width" => {
+                    let v = value()?;
+                    let width = v
+                        .parse()
+                        .map_err(|_| format!("invalid value '{}' for '{}'", v, flag))?;
+                    options.author_max_width = Some(width);
+                }
+                "--idle-timeout" => {
+                    let v = value()?;
+                    let seconds = v
+                        .parse()
+                        .map_err(|_| format!("invalid value '{}' for '{}'", v, flag))?;
+                    options.idle_timeout = Some(seconds);
+                }
+                "--stale-days" => {
+                    let v = value()?;
+                    let days = v
+                        .parse()
+                        .map_err(|_| format!("invalid value '{}' for '{}'", v, flag))?;
+                    options.stale_days = Some(days);
+                }
+                "--max-width" => {
+                    let v = value()?;
+                    let width = v
+                        .parse()
+                        .map_err(|_| format!("invalid value '{}' for '{}'", v, flag))?;
+                    options.max_width = Some(width);
+                }
+                _ if !arg.starts_with('-') && options.checkout.is_none() => {
+                    options.checkout = Some(arg)
+                }
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+        Ok(options)
+    }
+
+    // Whether any option leaves branches out of the list
+    pub fn filters(&self) -> bool {
+        self.glob.is_some()
+            || self.contains.is_some()
+            || self.committer.is_some()
+            || self.children_of.is_some()
+            || self.hide_merged
+            || self.unpushed
+            || self.ref_prefix.is_some()
+            || self.stdin
+    }
+
+    // Most branches to list, None for all of them. Output for scripts keeps to the
+    // most recent DEFAULT_LIMIT unless told otherwise, while the picker shows every
+    // branch and loads them in batches instead. --limit 0 lists all.
+    pub fn limit(&self) -> Option<usize> {
+        match self.limit {
+            Some(0) => None,
+            Some(limit) => Some(limit),
+            None if self.json || self.json_lines || self.export.is_some() || self.explain => {
+                Some(DEFAULT_LIMIT)
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn limit_defaults_to_fifty_for_script_output_only() {
+        assert_eq!(parse(&[]).unwrap().limit(), None);
+        assert_eq!(parse(&["--json"]).unwrap().limit(), Some(50));
+        assert_eq!(parse(&["--json", "--limit", "3"]).unwrap().limit(), Some(3));
+        assert_eq!(parse(&["--limit=3"]).unwrap().limit(), Some(3));
+    }
+
+    #[test]
+    fn limit_zero_lists_all() {
+        assert_eq!(parse(&["--json", "--limit", "0"]).unwrap().limit(), None);
+        assert_eq!(parse(&["--limit", "0"]).unwrap().limit(), None);
+    }
+
+    #[test]
+    fn filters_are_told_apart_from_other_options() {
+        assert!(!parse(&["--json", "--remote", "--limit", "3"])
+            .unwrap()
+            .filters());
+        assert!(parse(&["--glob", "x*"]).unwrap().filters());
+        assert!(parse(&["--committer", "me"]).unwrap().filters());
+        assert!(parse(&["--hide-merged"]).unwrap().filters());
+    }
+
+    #[test]
+    fn limit_must_be_a_number() {
+        assert_eq!(
+            parse(&["--limit", "abc"]).err().as_deref(),
+            Some("invalid value 'abc' for '--limit'")
+        );
+        assert!(parse(&["--limit", "-1"]).is_err());
+    }
+}
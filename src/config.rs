@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::ui::{Column, Flag};
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    pub keybind_branch: HashMap<char, String>,
+    pub columns: Option<Vec<Column>>,
+    // Glyphs shown in the flags column, all of them by default
+    pub flags: Option<Vec<Flag>>,
+    pub sha_length: Option<usize>,
+    pub current_first: bool,
+    pub highlight_style: Option<String>,
+    pub ref_namespaces: Vec<RefNamespace>,
+    // Ring the terminal bell instead of flashing the border on key presses that do nothing
+    pub bell: bool,
+    pub author_max_width: Option<usize>,
+    pub author_first_name: bool,
+    pub frecency: FrecencyWeights,
+    // Shell command run after every successful switch
+    pub exec: Option<String>,
+    // Seconds without a key press after which the picker quits
+    pub idle_timeout: Option<u64>,
+    // Milliseconds between checks for keys and between redraws while waiting on
+    // something, 50 and 250 by default
+    pub poll_interval_ms: Option<u64>,
+    pub tick_interval_ms: Option<u64>,
+    // Where branch names link to, with "{repo}" for the origin's web address and
+    // "{branch}" for the name
+    pub branch_url: Option<String>,
+    // Days without a commit after which a branch is shown in red
+    pub stale_days: Option<i64>,
+    // Write descriptions in $EDITOR rather than on the footer line
+    pub external_editor: bool,
+    pub parent: ParentSource,
+    pub keys: KeyBindings,
+    // Branches the picker refuses to delete, as names or patterns with * and ?
+    pub protected_branches: Vec<String>,
+}
+
+// Keys for the picker's main actions, replacing the built-in ones. The arrow keys and
+// Esc keep working whatever is set here.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub up: char,
+    pub down: char,
+    pub select: char,
+    pub quit: char,
+    pub delete: char,
+    pub filter: char,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            up: 'k',
+            down: 'j',
+            select: '\n',
+            quit: 'q',
+            delete: 'D',
+            filter: '/',
+        }
+    }
+}
+
+// How much commit recency and checkout history count towards the frecency order
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct FrecencyWeights {
+    pub commit: f64,
+    pub checkout: f64,
+}
+
+impl Default for FrecencyWeights {
+    fn default() -> FrecencyWeights {
+        FrecencyWeights {
+            commit: 1.0,
+            checkout: 1.0,
+        }
+    }
+}
+
+// Where stacked-branch tools record a branch's parent: a git config key, with "{}"
+// standing for the branch name, and optionally a trailer in the tip commit's message
+// for tools that keep it there instead
+#[derive(Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ParentSource {
+    pub config_key: String,
+    pub trailer: Option<String>,
+}
+
+impl Default for ParentSource {
+    fn default() -> ParentSource {
+        ParentSource {
+            config_key: String::from("branch.{}.parent"),
+            trailer: None,
+        }
+    }
+}
+
+// Extra refs to list, e.g. pull request heads, along with the name of the local
+// branch created when one is checked out. "{}" in the name is replaced by the part
+// of the ref matched by "*" in the glob.
+#[derive(Deserialize)]
+pub struct RefNamespace {
+    pub glob: String,
+    pub branch: String,
+}
+
+fn config_path() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("git-checkout-recent").join("config.toml"))
+}
+
+const REPO_CONFIG_FILE: &str = ".git-checkout-recent.toml";
+
+// Settings that run commands or build links, which a cloned repository must not be
+// able to set for whoever works in it
+const GLOBAL_ONLY_KEYS: &[&str] = &["exec", "branch-url", "ref-namespaces"];
+
+fn drop_global_only(value: &mut toml::Value, path: &Path) {
+    if let Some(table) = value.as_table_mut() {
+        for key in GLOBAL_ONLY_KEYS {
+            if table.remove(*key).is_some() {
+                eprintln!(
+                    "Ignoring {key} in {}: it is only read from the global config",
+                    path.display()
+                );
+            }
+        }
+    }
+}
+
+fn read_toml(path: &Path) -> Option<toml::Value> {
+    let contents = fs::read_to_string(path).ok()?;
+    match contents.parse() {
+        Ok(value) => Some(value),
+        Err(e) => {
+            eprintln!("Ignoring malformed config {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+const ENV_PREFIX: &str = "GIT_CHECKOUT_RECENT_";
+
+// Top-level settings from variables like GIT_CHECKOUT_RECENT_STALE_DAYS=30. Values
+// are read as TOML, so arrays and inline tables work too; anything that doesn't
+// parse is taken as a string.
+fn env_overrides(vars: impl Iterator<Item = (String, String)>) -> toml::Value {
+    let mut table = toml::value::Table::new();
+    for (name, raw) in vars {
+        let key = match name.strip_prefix(ENV_PREFIX) {
+            Some(key) if !key.is_empty() => key.to_lowercase().replace('_', "-"),
+            _ => continue,
+        };
+        let value = format!("value = {raw}")
+            .parse::<toml::Value>()
+            .ok()
+            .and_then(|mut parsed| parsed.as_table_mut()?.remove("value"))
+            .unwrap_or(toml::Value::String(raw));
+        table.insert(key, value);
+    }
+    toml::Value::Table(table)
+}
+
+// Settings in `overlay` replace those in `base`, merging tables key by key
+fn merge(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+impl Config {
+    // Reads the global config, with the repository's own .git-checkout-recent.toml
+    // taking precedence over it and GIT_CHECKOUT_RECENT_* variables over both
+    pub fn load(workdir: Option<&Path>) -> Config {
+        let mut value = toml::Value::Table(toml::value::Table::new());
+        let global = config_path().and_then(|path| read_toml(&path));
+        let local = workdir.and_then(|dir| {
+            let path = dir.join(REPO_CONFIG_FILE);
+            let mut value = read_toml(&path)?;
+            drop_global_only(&mut value, &path);
+            Some(value)
+        });
+        // env::vars would panic on a variable that isn't valid Unicode
+        let vars = env::vars_os().filter_map(|(name, value)| {
+            Some((name.into_string().ok()?, value.into_string().ok()?))
+        });
+        let env = env_overrides(vars);
+        for overlay in global.into_iter().chain(local).chain(Some(env)) {
+            merge(&mut value, overlay);
+        }
+        let mut config: Config = match value.try_into() {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Ignoring malformed config: {e}");
+                Config::default()
+            }
+        };
+        config.drop_conflicting_keys();
+        config
+    }
+
+    // The picker handles these before branch bindings, so a branch bound to one of
+    // them could never be checked out with it
+    fn reserved_keys(&self) -> [char; 8] {
+        let k = &self.keys;
+        [
+            k.up, k.down, k.select, k.quit, k.delete, k.filter, 'Q', '\t',
+        ]
+    }
+
+    fn drop_conflicting_keys(&mut self) {
+        let actions = &self.reserved_keys()[..6];
+        let repeated = (1..actions.len()).any(|i| actions[..i].contains(&actions[i]));
+        if repeated {
+            eprintln!("Ignoring [keys]: the same key is given to more than one action");
+            self.keys = KeyBindings::default();
+        }
+        let reserved = self.reserved_keys();
+        self.keybind_branch.retain(|key, name| {
+            let taken = reserved.contains(key);
+            if taken {
+                eprintln!("Ignoring keybind-branch {key:?} = {name:?}: the key has another action");
+            }
+            !taken
+        });
+    }
+}
+
+// Replaces the top-level `columns` setting in the config text, or adds it before the
+// first table, leaving the rest as it was written, comments included
+fn replace_columns(contents: &str, setting: &str) -> String {
+    let mut lines: Vec<&str> = contents.lines().collect();
+    let is_columns = |line: &str| {
+        line.trim_start()
+            .strip_prefix("columns")
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    };
+    let top_level_end = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    match lines[..top_level_end]
+        .iter()
+        .position(|line| is_columns(line))
+    {
+        Some(start) => {
+            // The array may span several lines
+            let mut depth = 0;
+            let mut end = start;
+            for (i, line) in lines.iter().enumerate().skip(start) {
+                depth += line.matches('[').count() as i64 - line.matches(']').count() as i64;
+                end = i;
+                if depth <= 0 {
+                    break;
+                }
+            }
+            lines.splice(start..=end, [setting]);
+        }
+        None => lines.insert(top_level_end, setting),
+    }
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    contents
+}
+
+// Stores the enabled columns in the config file, keeping any other settings in it
+pub fn save_columns(columns: &[Column]) -> Result<(), String> {
+    let path = config_path().ok_or("no config directory")?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.to_string()),
+    };
+    // Rewriting a file that doesn't parse could lose what the user meant by it
+    contents
+        .parse::<toml::Value>()
+        .map_err(|e| format!("{} is malformed: {e}", path.display()))?;
+    let columns = toml::Value::try_from(columns).map_err(|e| e.to_string())?;
+    let updated = replace_columns(&contents, &format!("columns = {columns}"));
+    let check = updated
+        .parse::<toml::Value>()
+        .map_err(|e| format!("could not update {}: {e}", path.display()))?;
+    if check.get("columns") != Some(&columns) {
+        return Err(format!("could not update {}", path.display()));
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, updated).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTING: &str = "columns = [\"name\"]";
+
+    fn parse(contents: &str) -> Config {
+        let mut config: Config = toml::from_str(contents).unwrap();
+        config.drop_conflicting_keys();
+        config
+    }
+
+    #[test]
+    fn branch_bindings_cannot_take_the_action_keys() {
+        let config = parse(
+            "[keys]\nup = \"p\"\n[keybind-branch]\np = \"a\"\nq = \"b\"\nk = \"c\"\nh = \"d\"\n",
+        );
+        let mut bound: Vec<_> = config.keybind_branch.keys().collect();
+        bound.sort();
+        // k is free once up is moved to p, and h never had an action
+        assert_eq!(bound, vec![&'h', &'k']);
+        assert_eq!(config.keys.up, 'p');
+    }
+
+    #[test]
+    fn environment_variables_are_read_as_toml_values() {
+        let vars = [
+            ("GIT_CHECKOUT_RECENT_STALE_DAYS", "30"),
+            ("GIT_CHECKOUT_RECENT_COLUMNS", "[\"author\"]"),
+            ("GIT_CHECKOUT_RECENT_KEYS", "{ up = \"p\" }"),
+            ("GIT_CHECKOUT_RECENT_EXEC", "echo switched"),
+            ("HOME", "/home/me"),
+        ];
+        let mut value: toml::Value = "stale-days = 10\nbell = true\n[keys]\ndown = \"n\"\n"
+            .parse()
+            .unwrap();
+        let vars = vars.iter().map(|(k, v)| (k.to_string(), v.to_string()));
+        merge(&mut value, env_overrides(vars));
+        let config: Config = value.try_into().unwrap();
+
+        assert_eq!(config.stale_days, Some(30));
+        assert!(config.bell);
+        assert!(matches!(config.columns.as_deref(), Some([Column::Author])));
+        assert_eq!((config.keys.up, config.keys.down), ('p', 'n'));
+        assert_eq!(config.exec.as_deref(), Some("echo switched"));
+    }
+
+    #[test]
+    fn keys_given_to_two_actions_are_ignored() {
+        let config = parse("[keys]\nup = \"x\"\ndown = \"x\"\n");
+        assert_eq!((config.keys.up, config.keys.down), ('k', 'j'));
+    }
+
+    #[test]
+    fn replace_columns_keeps_comments_and_other_settings() {
+        let contents =
+            "# my settings\nbell = true\ncolumns = [\"name\", \"author\"]\n\n[keys]\nup = \"p\"\n";
+        assert_eq!(
+            replace_columns(contents, SETTING),
+            "# my settings\nbell = true\ncolumns = [\"name\"]\n\n[keys]\nup = \"p\"\n"
+        );
+    }
+
+    #[test]
+    fn replace_columns_replaces_a_multi_line_array() {
+        let contents = "columns = [\n  \"name\",\n  \"author\",\n]\nbell = true\n";
+        assert_eq!(
+            replace_columns(contents, SETTING),
+            "columns = [\"name\"]\nbell = true\n"
+        );
+    }
+
+    #[test]
+    fn replace_columns_adds_the_setting_before_the_first_table() {
+        let contents = "bell = true\n[keybind-branch]\nm = \"main\"\n";
+        assert_eq!(
+            replace_columns(contents, SETTING),
+            "bell = true\ncolumns = [\"name\"]\n[keybind-branch]\nm = \"main\"\n"
+        );
+        assert_eq!(replace_columns("", SETTING), "columns = [\"name\"]\n");
+    }
+
+    #[test]
+    fn replace_columns_ignores_columns_keys_inside_tables() {
+        let contents = "[other]\ncolumns = 1\n";
+        assert_eq!(
+            replace_columns(contents, SETTING),
+            "columns = [\"name\"]\n[other]\ncolumns = 1\n"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::io;
+
+use git2::ErrorCode;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("not a git repository (or any of the parent directories)")]
+    NotARepo,
+    #[error("failed to open repo: {0}")]
+    RepoOpen(git2::Error),
+    #[error("repository is not in a clean state (in the middle of a merge?)")]
+    DirtyTree,
+    #[error("failed to checkout branch: {0}")]
+    CheckoutConflict(git2::Error),
+    #[error("HEAD is still on '{head}' but the working tree matches '{target}': {source}")]
+    InconsistentHead {
+        head: String,
+        target: String,
+        source: git2::Error,
+    },
+    #[error("not changing the repository in read-only mode")]
+    ReadOnly,
+    #[error("'{0}' is not a valid branch name")]
+    InvalidBranchName(String),
+    #[error("a branch named '{0}' already exists")]
+    BranchExists(String),
+    #[error("branch '{branch}' already exists and does not track '{upstream}'")]
+    TracksOtherBranch { branch: String, upstream: String },
+    #[error("'{0}' is not a local branch")]
+    NotABranch(String),
+    #[error("'{0}' is a protected branch")]
+    ProtectedBranch(String),
+    #[error("'{0}' is the checked out branch")]
+    BranchCheckedOut(String),
+    #[error("error rendering branch selection: {0}")]
+    Ui(#[from] io::Error),
+    #[error("{0}")]
+    Usage(String),
+    #[error("no such branch '{0}'")]
+    NoSuchBranch(String),
+    #[error("no such branch or revision '{spec}': {}", .source.message())]
+    NoSuchRevision { spec: String, source: git2::Error },
+    #[error("failed to resolve commit '{spec}': {source}")]
+    UnknownCommit { spec: String, source: git2::Error },
+    #[error("failed to resolve HEAD: {0}")]
+    NoHead(git2::Error),
+    #[error("no previous branch to switch to")]
+    NoPreviousBranch,
+    #[error("no checkout to undo")]
+    NothingToUndo,
+    #[error("HEAD has moved since switching from '{from}' to '{to}', not undoing")]
+    HeadMoved { from: String, to: String },
+    #[error("the last checkout started from a detached HEAD that was not recorded, not undoing")]
+    UndoDetached,
+    #[error("no remote selected")]
+    NoRemoteSelected,
+    #[error("failed to create branch '{name}': {source}")]
+    CreateBranch { name: String, source: Box<Error> },
+    #[error("failed to create branch '{name}' from '{remote}': {source}")]
+    TrackRemote {
+        name: String,
+        remote: String,
+        source: Box<Error>,
+    },
+    #[error("cannot fast-forward '{0}', a merge is needed")]
+    Diverged(String),
+    #[error("failed to pull: {0}")]
+    Pull(Box<Error>),
+    #[error("failed to stash local changes: {0}")]
+    Stash(Box<Error>),
+    #[error("failed to write branches as JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("failed to write '{path}': {source}")]
+    Export { path: String, source: io::Error },
+    #[error(transparent)]
+    Git(git2::Error),
+}
+
+impl Error {
+    // 2 and 3 mean nothing was tried: the repository was busy or could not be opened
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::DirtyTree => 2,
+            Error::NotARepo | Error::RepoOpen(_) => 3,
+            _ => 1,
+        }
+    }
+}
+
+impl From<git2::Error> for Error {
+    fn from(e: git2::Error) -> Error {
+        match e.code() {
+            ErrorCode::Conflict => Error::CheckoutConflict(e),
+            _ => Error::Git(e),
+        }
+    }
+}
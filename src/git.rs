@@ -1,35 +1,187 @@
+use std::collections::HashMap;
 use std::fmt;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::UNIX_EPOCH;
 
+use git2::build::CheckoutBuilder;
 use git2::Branch;
 use git2::BranchType;
+use git2::ConfigLevel;
+use git2::Cred;
+use git2::Delta;
+use git2::DescribeFormatOptions;
+use git2::DescribeOptions;
+use git2::ErrorCode;
+use git2::FetchOptions;
+use git2::FileMode;
+use git2::Oid;
+use git2::Reference;
+use git2::RemoteCallbacks;
 use git2::Repository;
+use git2::Signature;
+use git2::Sort;
+use git2::StatusOptions;
 
 use chrono::offset::FixedOffset;
 use chrono::offset::TimeZone;
+use chrono::offset::Utc;
+use chrono::DateTime;
 use chrono::NaiveDateTime;
 use chrono_humanize::HumanTime;
+use serde::Serialize;
 
+use super::config::{FrecencyWeights, ParentSource, RefNamespace};
+use super::error::Error;
+use super::profile;
+
+#[derive(Clone, Serialize)]
 pub struct BranchRecord {
     pub name: String,
     pub commit_sha: String,
+    // Shortest unambiguous abbreviation of the SHA, honouring core.abbrev
+    pub short_sha: String,
     pub time_seconds: i64,
     pub offset_minutes: i32,
     pub summary: String,
+    // The whole commit message, loaded with the other details
+    pub message: String,
     pub ref_name: String,
+    // Name of the remote the upstream branch belongs to, e.g. "origin"
+    pub upstream_remote: Option<String>,
+    pub upstream_sha: Option<String>,
+    // Commits only on this branch and only on its upstream, once populate_ahead_behind ran
+    pub ahead_behind: Option<(usize, usize)>,
     pub author_name: String,
+    pub author_email: String,
+    pub committer_name: String,
+    pub committer_email: String,
     pub is_current_branch: bool,
+    // Points at the same commit as HEAD without being the checked out branch
+    pub is_at_head: bool,
+    pub is_default: bool,
+    // Checked out before the current branch, which `git checkout -` would switch to
+    pub is_previous: bool,
+    pub recent_commit_count: usize,
+    // Summary, author and recent activity are only filled in by load_details
+    #[serde(skip)]
+    pub has_details: bool,
+    // Commits not yet in the base branch, only computed when sorting by size
+    pub unmerged_commits: Option<usize>,
+    // Blend of commit and checkout recency, only computed when sorting by frecency
+    pub frecency: Option<f64>,
+    // Most recent checkout of the branch in the HEAD reflog, found along with frecency
+    pub last_checkout_seconds: Option<i64>,
+    // How the branch relates to remote branches of the same name that were folded
+    // into it, e.g. "2 ahead of origin/main"
+    pub remote_status: Option<String>,
+    // When the branch was created according to its reflog, falling back to the commit
+    // time. Only computed when sorting by creation time or showing it.
+    pub created_seconds: Option<i64>,
+    // branch.<name>.description, loaded with the other details
+    pub description: Option<String>,
+    // Other branches at the same commit, hidden behind this one by --dedup-tips
+    #[serde(skip)]
+    pub aliases: Vec<BranchRecord>,
+    // Newest stash made on the branch, as in stash@{N}, once populate_stashes ran
+    pub stash_index: Option<usize>,
+    // Branch this one is stacked on, once populate_parents ran
+    pub parent: Option<String>,
+    // An upstream is configured but its ref no longer exists, loaded with the details
+    pub upstream_gone: bool,
+    // Checked out in a worktree other than this one, once populate_worktrees ran
+    pub in_other_worktree: bool,
 }
 
 impl BranchRecord {
+    pub fn is_remote(&self) -> bool {
+        self.ref_name.starts_with("refs/remotes/")
+    }
+
+    // The SHA cut to the given length, or to the repository's unambiguous length
+    pub fn abbreviated_sha(&self, length: Option<usize>) -> &str {
+        match length {
+            Some(length) => self.commit_sha.get(..length).unwrap_or(&self.commit_sha),
+            None if !self.short_sha.is_empty() => &self.short_sha,
+            None => self.commit_sha.get(..8).unwrap_or(&self.commit_sha),
+        }
+    }
+
     pub fn pretty_format_date(&self) -> String {
-        let naive_dt = NaiveDateTime::from_timestamp(self.time_seconds, 0);
-        let offset = FixedOffset::east(self.offset_minutes * 60);
-        let dt = offset.from_utc_datetime(&naive_dt);
-        let humanized_dt = HumanTime::from(dt);
-        humanized_dt.to_string()
+        format_relative_time(self.time_seconds, self.offset_minutes, Utc::now())
+    }
+
+    // The commit time in the commit's own time zone
+    pub fn commit_time(&self) -> DateTime<FixedOffset> {
+        commit_offset(self.offset_minutes)
+            .from_utc_datetime(&NaiveDateTime::from_timestamp(self.time_seconds, 0))
+    }
+
+    // "2024-06-01 14:30", in the commit's own time zone
+    pub fn format_date_absolute(&self) -> String {
+        self.commit_time().format("%Y-%m-%d %H:%M").to_string()
+    }
+
+    // Relative dates are measured from `now`, which callers can fix for reproducible output
+    pub fn format_date(&self, format: DateFormat, now: DateTime<Utc>) -> String {
+        let relative = || format_relative_time(self.time_seconds, self.offset_minutes, now);
+        let iso = || self.commit_time().format("%Y-%m-%d").to_string();
+        match format {
+            DateFormat::Relative => relative(),
+            DateFormat::Iso => iso(),
+            DateFormat::Both => format!("{} ({})", relative(), iso()),
+            DateFormat::Absolute => self.format_date_absolute(),
+        }
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum DateFormat {
+    // "2 days ago"
+    #[default]
+    Relative,
+    // "2024-06-01", in the commit's own time zone
+    Iso,
+    // "2 days ago (2024-06-01)"
+    Both,
+    // "2024-06-01 14:30", toggled to in the picker
+    Absolute,
+}
+
+// Imported or corrupt histories can have offsets beyond a day, which FixedOffset
+// rejects, so those dates are shown in UTC instead
+fn commit_offset(offset_minutes: i32) -> FixedOffset {
+    offset_minutes
+        .checked_mul(60)
+        .and_then(FixedOffset::east_opt)
+        .unwrap_or_else(|| FixedOffset::east(0))
+}
+
+pub fn format_relative_time(time_seconds: i64, offset_minutes: i32, now: DateTime<Utc>) -> String {
+    let naive_dt = NaiveDateTime::from_timestamp(time_seconds, 0);
+    let offset = commit_offset(offset_minutes);
+    let dt = offset.from_utc_datetime(&naive_dt);
+    let humanized_dt = HumanTime::from(dt.signed_duration_since(now));
+    humanized_dt.to_string()
+}
+
+// Short age such as "45s", "2h" or "3w", for places where space is tight
+pub fn format_compact_age(time_seconds: i64, now: DateTime<Utc>) -> String {
+    let seconds = (now.timestamp() - time_seconds).max(0);
+    let (value, unit) = match seconds {
+        s if s < 60 => (s, "s"),
+        s if s < 60 * 60 => (s / 60, "m"),
+        s if s < 24 * 60 * 60 => (s / (60 * 60), "h"),
+        s if s < 7 * 24 * 60 * 60 => (s / (24 * 60 * 60), "d"),
+        s if s < 30 * 24 * 60 * 60 => (s / (7 * 24 * 60 * 60), "w"),
+        s if s < 365 * 24 * 60 * 60 => (s / (30 * 24 * 60 * 60), "mo"),
+        s => (s / (365 * 24 * 60 * 60), "y"),
+    };
+    format!("{value}{unit}")
+}
+
 impl fmt::Display for BranchRecord {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -43,40 +195,230 @@ impl fmt::Display for BranchRecord {
     }
 }
 
+// One-line summary of a set of branches: how many, who they belong to and their age span
+pub fn branch_stats(records: &[BranchRecord], now: DateTime<Utc>) -> String {
+    let mut authors: Vec<(&str, usize)> = vec![];
+    for record in records {
+        match authors
+            .iter_mut()
+            .find(|(name, _)| *name == record.author_name)
+        {
+            Some((_, count)) => *count += 1,
+            None => authors.push((&record.author_name, 1)),
+        }
+    }
+    authors.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    let mut by_author: Vec<String> = authors
+        .iter()
+        .take(3)
+        .map(|(name, count)| format!("{name} {count}"))
+        .collect();
+    if authors.len() > 3 {
+        by_author.push(format!("+{} more", authors.len() - 3));
+    }
+
+    let mut stats = format!("{} branches", records.len());
+    if !by_author.is_empty() {
+        stats += &format!(" | by author: {}", by_author.join(", "));
+    }
+    let newest = records.iter().map(|r| r.time_seconds).max();
+    let oldest = records.iter().map(|r| r.time_seconds).min();
+    if let (Some(newest), Some(oldest)) = (newest, oldest) {
+        stats += &format!(
+            " | newest {}, oldest {}",
+            format_compact_age(newest, now),
+            format_compact_age(oldest, now)
+        );
+    }
+    stats
+}
+
+// Branch activity is measured over this window, and the walk stops after this many commits
+pub const RECENT_ACTIVITY_DAYS: i64 = 7;
+const MAX_RECENT_COMMITS: usize = 99;
+
+fn count_recent_commits(repo: &Repository, tip: Oid, cutoff_seconds: i64) -> usize {
+    let mut walk = match repo.revwalk() {
+        Ok(walk) => walk,
+        Err(_) => return 0,
+    };
+    if walk.set_sorting(Sort::TIME).is_err() || walk.push(tip).is_err() {
+        return 0;
+    }
+    walk.flatten()
+        .filter_map(|oid| repo.find_commit(oid).ok())
+        .take_while(|commit| commit.time().seconds() >= cutoff_seconds)
+        .take(MAX_RECENT_COMMITS)
+        .count()
+}
+
+// Repository-wide facts that every parsed branch is compared against
+struct BranchContext {
+    head_branch_refname: Option<String>,
+    head_oid: Option<Oid>,
+    // HEAD is on a branch whose name could not be found, so the branch at the HEAD
+    // commit is taken to be the current one
+    match_head_oid: bool,
+    default_refname: Option<String>,
+    previous_refname: Option<String>,
+}
+
+impl BranchContext {
+    fn new(repo: &Repository) -> BranchContext {
+        let head_branch_refname = get_current_branch_refname(repo);
+        let is_detached = repo.head_detached().unwrap_or(false);
+        BranchContext {
+            match_head_oid: head_branch_refname.is_none() && !is_detached,
+            head_branch_refname,
+            head_oid: repo.refname_to_id("HEAD").ok(),
+            default_refname: default_branch_name(repo).map(|name| format!("refs/heads/{name}")),
+            previous_refname: previous_branch_name(repo).map(|name| format!("refs/heads/{name}")),
+        }
+    }
+}
+
+// The branch the repository is centred on: whatever origin/HEAD points at, falling
+// back to init.defaultBranch
+pub fn default_branch_name(repo: &Repository) -> Option<String> {
+    origin_head_branch(repo).or_else(|| init_default_branch(repo))
+}
+
+fn origin_head_branch(repo: &Repository) -> Option<String> {
+    let reference = repo.find_reference("refs/remotes/origin/HEAD").ok()?;
+    reference
+        .symbolic_target()
+        .and_then(|target| target.strip_prefix("refs/remotes/origin/"))
+        .map(String::from)
+}
+
+fn init_default_branch(repo: &Repository) -> Option<String> {
+    repo.config()
+        .and_then(|config| config.get_string("init.defaultBranch"))
+        .ok()
+}
+
 fn parse_local_branch(
+    repo: &Repository,
     branch: &Branch,
-    head_branch_refname: &Option<String>,
+    context: &BranchContext,
 ) -> Option<BranchRecord> {
-    let branch_name = branch.name().ok()??.to_string();
-
     let reference = branch.get();
-    let ref_name = reference.name()?.to_string();
+    let ref_name = String::from_utf8_lossy(reference.name_bytes()).into_owned();
+    // Refs outside refs/heads have no branch name; callers name those themselves
+    let branch_name = match branch.name_bytes() {
+        Ok(name) => String::from_utf8_lossy(name).into_owned(),
+        Err(_) => ref_name.clone(),
+    };
 
-    let mut is_current_branch = false;
-    if let Some(current) = head_branch_refname {
-        is_current_branch = ref_name == current.as_str();
-    }
+    let (upstream_remote, upstream_sha) = match branch.upstream() {
+        Ok(upstream) => (
+            repo.branch_upstream_remote(&ref_name)
+                .ok()
+                .and_then(|remote| remote.as_str().map(String::from)),
+            upstream.get().target().map(|oid| oid.to_string()),
+        ),
+        Err(_) => (None, None),
+    };
+
+    let is_default = context.default_refname.as_deref() == Some(ref_name.as_str());
+    let is_previous = context.previous_refname.as_deref() == Some(ref_name.as_str());
 
     let commit = reference.peel_to_commit().ok()?;
+    let is_current_branch = match &context.head_branch_refname {
+        Some(current) => ref_name == current.as_str(),
+        None => context.match_head_oid && context.head_oid == Some(commit.id()),
+    };
     let commit_sha = commit.id().to_string();
+    let is_at_head = !is_current_branch && context.head_oid == Some(commit.id());
     let time_seconds = commit.time().seconds();
     let offset_minutes = commit.time().offset_minutes();
-    let summary = commit.summary()?.to_string();
-    let author_name = commit.author().name()?.to_string();
+    let committer = commit.committer();
+    let committer_name = String::from_utf8_lossy(committer.name_bytes()).into_owned();
+    let committer_email = String::from_utf8_lossy(committer.email_bytes()).into_owned();
 
     let record = BranchRecord {
         name: branch_name,
         commit_sha,
+        short_sha: String::new(),
         time_seconds,
         offset_minutes,
-        summary,
+        summary: String::new(),
+        message: String::new(),
         ref_name,
-        author_name,
+        upstream_remote,
+        upstream_sha,
+        ahead_behind: None,
+        author_name: String::new(),
+        author_email: String::new(),
+        committer_name,
+        committer_email,
         is_current_branch,
+        is_at_head,
+        is_default,
+        is_previous,
+        recent_commit_count: 0,
+        has_details: false,
+        unmerged_commits: None,
+        frecency: None,
+        last_checkout_seconds: None,
+        remote_status: None,
+        created_seconds: None,
+        description: None,
+        aliases: vec![],
+        stash_index: None,
+        parent: None,
+        upstream_gone: false,
+        in_other_worktree: false,
     };
     Some(record)
 }
 
+// Second pass over a branch that is actually going to be shown. Listing only needs
+// the commit time, so this is skipped for branches that never make it on screen.
+pub fn load_details(repo: &Repository, record: &mut BranchRecord) {
+    if record.has_details {
+        return;
+    }
+    profile::time("load details", || load_details_now(repo, record));
+}
+
+fn load_details_now(repo: &Repository, record: &mut BranchRecord) {
+    record.has_details = true;
+    let commit = match Oid::from_str(&record.commit_sha).and_then(|oid| repo.find_commit(oid)) {
+        Ok(commit) => commit,
+        Err(_) => return,
+    };
+    if let Ok(short_id) = commit.as_object().short_id() {
+        record.short_sha = short_id.as_str().unwrap_or_default().to_string();
+    }
+    record.summary =
+        String::from_utf8_lossy(commit.summary_bytes().unwrap_or_default()).into_owned();
+    record.message = String::from_utf8_lossy(commit.message_bytes())
+        .trim_end()
+        .to_string();
+    record.author_name = String::from_utf8_lossy(commit.author().name_bytes()).into_owned();
+    record.author_email = String::from_utf8_lossy(commit.author().email_bytes()).into_owned();
+    if record.ahead_behind.is_none() {
+        record.ahead_behind = ahead_behind(repo, record);
+    }
+    if record.created_seconds.is_none() {
+        record.created_seconds = Some(creation_time(repo, record));
+    }
+    if let Some(name) = record.ref_name.strip_prefix("refs/heads/") {
+        let config = repo.config().ok();
+        let get = |key: String| config.as_ref()?.get_string(&key).ok();
+        record.description =
+            get(format!("branch.{name}.description")).filter(|description| !description.is_empty());
+        record.upstream_gone =
+            record.upstream_sha.is_none() && get(format!("branch.{name}.merge")).is_some();
+    }
+    let cutoff_seconds = Utc::now().timestamp() - RECENT_ACTIVITY_DAYS * 24 * 60 * 60;
+    if record.time_seconds >= cutoff_seconds {
+        record.recent_commit_count = count_recent_commits(repo, commit.id(), cutoff_seconds);
+    }
+}
+
 fn get_current_branch_refname(repo: &Repository) -> Option<String> {
     if let Ok(is_detached) = repo.head_detached() {
         if is_detached {
@@ -88,36 +430,1441 @@ fn get_current_branch_refname(repo: &Repository) -> Option<String> {
             return Some(name.to_string());
         }
     };
-    None
+    // Resolving HEAD can fail on unusual packed refs, while reading the symbolic
+    // ref itself still works
+    let head = repo.find_reference("HEAD").ok()?;
+    head.symbolic_target().map(String::from)
+}
+
+// The checked out branch, read from HEAD rather than picked out of listed records so
+// it is found even when filters leave it out of the list
+pub fn current_branch(repo: &Repository) -> Option<BranchRecord> {
+    let context = BranchContext::new(repo);
+    let reference = repo
+        .find_reference(context.head_branch_refname.as_deref()?)
+        .ok()?;
+    parse_local_branch(repo, &Branch::wrap(reference), &context)
+}
+
+// Web address of the origin remote, e.g. "https://github.com/org/repo" for both
+// "git@github.com:org/repo.git" and "https://github.com/org/repo.git"
+pub fn origin_web_url(repo: &Repository) -> Option<String> {
+    let remote = repo.find_remote("origin").ok()?;
+    let url = remote.url()?;
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let (host, path) = if let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .or_else(|| url.strip_prefix("ssh://"))
+    {
+        let (host, path) = rest.split_once('/')?;
+        // Neither credentials nor the SSH port belong in the web address
+        let host = host.rsplit('@').next()?;
+        (host.split(':').next()?, path)
+    } else {
+        // scp-like syntax, "user@host:path"
+        let (host, path) = url.split_once(':')?;
+        (host.rsplit('@').next()?, path)
+    };
+    if host.is_empty() || path.is_empty() {
+        return None;
+    }
+    Some(format!("https://{host}/{path}"))
+}
+
+pub fn head_shorthand(repo: &Repository) -> Option<String> {
+    let head = repo.head().ok()?;
+    Some(String::from_utf8_lossy(head.shorthand_bytes()).into_owned())
+}
+
+// Where HEAD is, in a form that checking out gets back to: the branch name, or the
+// commit when HEAD is detached, whose shorthand is just "HEAD"
+pub fn head_position(repo: &Repository) -> Option<String> {
+    if repo.head_detached().unwrap_or(false) {
+        return repo.refname_to_id("HEAD").ok().map(|oid| oid.to_string());
+    }
+    head_shorthand(repo)
+}
+
+// Looks up the given local branches instead of listing all of them, skipping unknown names
+pub fn extract_named_branches(repo: &Repository, names: &[String]) -> Vec<BranchRecord> {
+    let context = BranchContext::new(repo);
+
+    let mut records = vec![];
+    for name in names {
+        match repo.find_branch(name, BranchType::Local) {
+            Ok(branch) => {
+                if let Some(record) = parse_local_branch(repo, &branch, &context) {
+                    records.push(record)
+                }
+            }
+            Err(_) => println!("Skipping unknown branch '{name}'"),
+        }
+    }
+    records
+}
+
+// Lists refs matching each namespace glob under the local branch names they would
+// be checked out as
+pub fn extract_namespace_refs(repo: &Repository, namespaces: &[RefNamespace]) -> Vec<BranchRecord> {
+    let context = BranchContext::new(repo);
+    let mut records = vec![];
+    for namespace in namespaces {
+        let (prefix, suffix) = namespace
+            .glob
+            .split_once('*')
+            .unwrap_or((namespace.glob.as_str(), ""));
+        let references = match repo.references_glob(&namespace.glob) {
+            Ok(references) => references,
+            Err(e) => {
+                println!("Skipping refs '{}': {e}", namespace.glob);
+                continue;
+            }
+        };
+        for reference in references.flatten() {
+            let ref_name = String::from_utf8_lossy(reference.name_bytes()).into_owned();
+            let matched = ref_name
+                .strip_prefix(prefix)
+                .and_then(|rest| rest.strip_suffix(suffix))
+                .unwrap_or(&ref_name)
+                .to_string();
+            if let Some(mut record) = parse_local_branch(repo, &Branch::wrap(reference), &context) {
+                record.name = namespace.branch.replace("{}", &matched);
+                records.push(record);
+            }
+        }
+    }
+    records
+}
+
+// Remote-tracking branches, named like "origin/main". The symbolic "origin/HEAD"
+// refs are left out.
+pub fn extract_remote_branches(repo: &Repository) -> Vec<BranchRecord> {
+    let context = BranchContext::new(repo);
+    let branches = match repo.branches(Some(BranchType::Remote)) {
+        Ok(branches) => branches,
+        Err(e) => {
+            println!("Failed to list remote branches: {e}");
+            return vec![];
+        }
+    };
+    branches
+        .flatten()
+        .filter(|(branch, _)| branch.get().symbolic_target_bytes().is_none())
+        .filter_map(|(branch, _)| parse_local_branch(repo, &branch, &context))
+        .collect()
+}
+
+// Keeps one branch per commit, preferring the current branch, then the default one,
+// then whichever comes first. The others are kept as its aliases.
+pub fn collapse_shared_tips(records: &mut Vec<BranchRecord>) {
+    let mut kept: Vec<BranchRecord> = vec![];
+    for record in records.drain(..) {
+        let existing = kept.iter_mut().find(|r| r.commit_sha == record.commit_sha);
+        match existing {
+            Some(existing) => {
+                let rank = |r: &BranchRecord| (!r.is_current_branch, !r.is_default);
+                if rank(&record) < rank(existing) {
+                    let mut record = record;
+                    std::mem::swap(existing, &mut record);
+                    existing.aliases = std::mem::take(&mut record.aliases);
+                    existing.aliases.push(record);
+                } else {
+                    existing.aliases.push(record);
+                }
+            }
+            None => kept.push(record),
+        }
+    }
+    *records = kept;
+}
+
+// Folds remote-tracking branches into the local branch of the same name, so each
+// branch is listed once. The local one is kept and told how the remote one compares.
+pub fn merge_remote_duplicates(repo: &Repository, records: &mut Vec<BranchRecord>) {
+    let mut statuses: Vec<(usize, String)> = vec![];
+    let mut duplicates = vec![];
+    for (i, remote) in records.iter().enumerate() {
+        let local_ref = match remote_branch_local_name(repo, &remote.ref_name) {
+            Some(name) => format!("refs/heads/{name}"),
+            None => continue,
+        };
+        let local = match records.iter().position(|r| r.ref_name == local_ref) {
+            Some(local) => local,
+            None => continue,
+        };
+        let divergence = match (
+            Oid::from_str(&records[local].commit_sha),
+            Oid::from_str(&remote.commit_sha),
+        ) {
+            (Ok(local), Ok(other)) => repo.graph_ahead_behind(local, other).ok(),
+            _ => None,
+        };
+        let status = match divergence {
+            Some((0, 0)) => format!("same as {}", remote.name),
+            Some((ahead, 0)) => format!("{ahead} ahead of {}", remote.name),
+            Some((0, behind)) => format!("{behind} behind {}", remote.name),
+            Some((ahead, behind)) => format!("+{ahead} -{behind} from {}", remote.name),
+            None => format!("also on {}", remote.name),
+        };
+        statuses.push((local, status));
+        duplicates.push(i);
+    }
+    for (local, status) in statuses {
+        let record = &mut records[local];
+        record.remote_status = Some(match record.remote_status.take() {
+            Some(existing) => format!("{existing}, {status}"),
+            None => status,
+        });
+    }
+    for i in duplicates.into_iter().rev() {
+        records.remove(i);
+    }
+}
+
+// Remotes that have a branch with the given name, for creating a tracking branch
+pub fn remotes_with_branch(repo: &Repository, name: &str) -> Vec<String> {
+    let remotes = match repo.remotes() {
+        Ok(remotes) => remotes,
+        Err(_) => return vec![],
+    };
+    remotes
+        .iter()
+        .flatten()
+        .filter(|remote| {
+            repo.find_branch(&format!("{remote}/{name}"), BranchType::Remote)
+                .is_ok()
+        })
+        .map(String::from)
+        .collect()
+}
+
+// The checkout.defaultRemote setting git uses to pick between remotes with the same branch
+pub fn default_remote(repo: &Repository) -> Option<String> {
+    repo.config()
+        .and_then(|config| config.get_string("checkout.defaultRemote"))
+        .ok()
+}
+
+// Creates a local branch tracking `<remote>/<name>`, like `git checkout <name>` does
+// when only a remote has it
+pub fn create_tracking_branch(
+    repo: &Repository,
+    remote: &str,
+    name: &str,
+) -> Result<Option<BranchRecord>, Error> {
+    let upstream = format!("{remote}/{name}");
+    let commit = repo
+        .find_branch(&upstream, BranchType::Remote)?
+        .get()
+        .peel_to_commit()?;
+    let mut branch = repo.branch(name, &commit, false)?;
+    branch.set_upstream(Some(&upstream))?;
+    Ok(parse_local_branch(repo, &branch, &BranchContext::new(repo)))
+}
+
+// Creates a local branch at the HEAD commit, refusing names git would not accept
+pub fn create_branch(repo: &Repository, name: &str) -> Result<Option<BranchRecord>, Error> {
+    if !Reference::is_valid_name(&format!("refs/heads/{name}")) {
+        return Err(Error::InvalidBranchName(name.to_string()));
+    }
+    if repo.find_branch(name, BranchType::Local).is_ok() {
+        return Err(Error::BranchExists(name.to_string()));
+    }
+    let commit = repo.head()?.peel_to_commit()?;
+    let branch = repo.branch(name, &commit, false)?;
+    Ok(parse_local_branch(repo, &branch, &BranchContext::new(repo)))
+}
+
+// Branch that was checked out before the current one, found the same way as
+// `git checkout -` from the HEAD reflog
+pub fn previous_branch_name(repo: &Repository) -> Option<String> {
+    let reflog = repo.reflog("HEAD").ok()?;
+    let name = reflog.iter().find_map(|entry| {
+        let message = entry.message()?;
+        let moved = message.strip_prefix("checkout: moving from ")?;
+        let (from, _) = moved.split_once(" to ")?;
+        Some(from.to_string())
+    });
+    name
 }
 
 pub fn extract_local_branches(repo: &Repository) -> Vec<BranchRecord> {
+    extract_branches_with_prefix(repo, None)
+}
+
+pub fn extract_branches_with_prefix(repo: &Repository, prefix: Option<&str>) -> Vec<BranchRecord> {
     let mut records: Vec<BranchRecord> = Vec::new();
+    for_each_local_branch(repo, prefix, |record| {
+        records.push(record);
+        true
+    });
+    records
+}
+
+// Hands each local branch to the callback as soon as it is parsed, so callers can
+// stream them out. Stops early once the callback returns false.
+//
+// With a prefix such as "feature/", only refs under it are read from the ref
+// database instead of enumerating every branch.
+pub fn for_each_local_branch<F: FnMut(BranchRecord) -> bool>(
+    repo: &Repository,
+    prefix: Option<&str>,
+    mut f: F,
+) {
+    let context = BranchContext::new(repo);
 
-    let current_branch_refname = get_current_branch_refname(repo);
+    if let Some(prefix) = prefix {
+        let glob = if prefix.starts_with("refs/") {
+            format!("{prefix}*")
+        } else {
+            format!("refs/heads/{prefix}*")
+        };
+        let references = match repo.references_glob(&glob) {
+            Ok(references) => references,
+            Err(e) => {
+                println!("Failed to list branches under '{prefix}': {e}");
+                return;
+            }
+        };
+        let mut references = references;
+        while let Some(reference) = profile::time("enumerate refs", || references.next()) {
+            match reference {
+                Ok(reference) => {
+                    let branch = Branch::wrap(reference);
+                    let record = profile::time("parse branches", || {
+                        parse_local_branch(repo, &branch, &context)
+                    });
+                    if let Some(record) = record {
+                        if !f(record) {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => println!("error in branch: {e}"),
+            }
+        }
+        return;
+    }
 
     match repo.branches(Some(BranchType::Local)) {
-        Ok(branches) => {
-            for branch in branches {
+        Ok(mut branches) => {
+            while let Some(branch) = profile::time("enumerate refs", || branches.next()) {
                 match branch {
                     Ok((branch, _)) => {
-                        if let Some(record) = parse_local_branch(&branch, &current_branch_refname) {
-                            records.push(record)
+                        let record = profile::time("parse branches", || {
+                            parse_local_branch(repo, &branch, &context)
+                        });
+                        if let Some(record) = record {
+                            if !f(record) {
+                                return;
+                            }
                         }
                     }
                     Err(e) => println!("error in branch: {e}"),
                 }
             }
         }
-        Err(e) => panic!("failed to get branches: {}", e),
+        // Listed as no branches rather than crashing, e.g. on unreadable refs
+        Err(e) => println!("Failed to list branches: {e}"),
     };
+}
 
-    records
+fn hooks_dir(repo: &Repository) -> PathBuf {
+    let configured = repo
+        .config()
+        .and_then(|config| config.get_path("core.hooksPath"));
+    match configured {
+        // Relative hook paths are resolved against the top of the working tree
+        Ok(path) if path.is_relative() => repo.workdir().unwrap_or_else(|| repo.path()).join(path),
+        Ok(path) => path,
+        Err(_) => repo.path().join("hooks"),
+    }
+}
+
+// Runs the post-checkout hook like `git checkout` does after switching branches.
+// A missing or non-executable hook is silently skipped.
+fn run_post_checkout_hook(repo: &Repository, old_head: Oid, new_head: Oid) {
+    let hook = hooks_dir(repo).join("post-checkout");
+    let is_executable = fs::metadata(&hook)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false);
+    if !is_executable {
+        return;
+    }
+    let _ = Command::new(&hook)
+        .arg(old_head.to_string())
+        .arg(new_head.to_string())
+        .arg("1")
+        .current_dir(repo.workdir().unwrap_or_else(|| repo.path()))
+        .status();
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Recency,
+    Size,
+    Frecency,
+    Created,
+}
+
+pub fn sort_records(records: &mut [BranchRecord], key: SortKey) {
+    records.sort_by_key(|r| std::cmp::Reverse(r.time_seconds));
+    if key == SortKey::Size {
+        // Stable sort, so equally sized branches stay in recency order
+        records.sort_by_key(|r| std::cmp::Reverse(r.unmerged_commits.unwrap_or(0)));
+    }
+    if key == SortKey::Frecency {
+        records.sort_by(|a, b| {
+            let score = |r: &BranchRecord| r.frecency.unwrap_or(0.0);
+            score(b).total_cmp(&score(a))
+        });
+    }
+    if key == SortKey::Created {
+        records.sort_by_key(|r| std::cmp::Reverse(r.created_seconds.unwrap_or(r.time_seconds)));
+    }
+}
+
+// The time of the oldest entry in the branch's reflog
+fn creation_time(repo: &Repository, record: &BranchRecord) -> i64 {
+    repo.reflog(&record.ref_name)
+        .ok()
+        .and_then(|reflog| {
+            let oldest = reflog.len().checked_sub(1)?;
+            reflog
+                .get(oldest)
+                .map(|entry| entry.committer().when().seconds())
+        })
+        .unwrap_or(record.time_seconds)
+}
+
+pub fn populate_creation_times(repo: &Repository, records: &mut [BranchRecord]) {
+    for record in records.iter_mut() {
+        record.created_seconds = Some(creation_time(repo, record));
+    }
+}
+
+// Refs checked out by the other worktrees sharing this repository, read from their
+// HEAD files: the main one's in the common directory and linked ones' under worktrees/
+fn other_worktree_heads(repo: &Repository) -> Vec<String> {
+    let common = match fs::read_to_string(repo.path().join("commondir")) {
+        Ok(dir) => repo.path().join(dir.trim()),
+        Err(_) => repo.path().to_path_buf(),
+    };
+    let mut heads = vec![common.join("HEAD")];
+    if let Ok(entries) = fs::read_dir(common.join("worktrees")) {
+        heads.extend(entries.flatten().map(|entry| entry.path().join("HEAD")));
+    }
+    let own = repo.path().join("HEAD");
+    heads
+        .into_iter()
+        .filter(|head| fs::canonicalize(head).ok() != fs::canonicalize(&own).ok())
+        .filter_map(|head| fs::read_to_string(head).ok())
+        .filter_map(|contents| Some(contents.strip_prefix("ref: ")?.trim().to_string()))
+        .collect()
+}
+
+pub fn populate_worktrees(repo: &Repository, records: &mut [BranchRecord]) {
+    let heads = other_worktree_heads(repo);
+    for record in records.iter_mut() {
+        record.in_other_worktree = heads.contains(&record.ref_name);
+    }
+}
+
+// Value of the last "Key: value" line in the closing paragraph of a commit message,
+// with the key matched case-insensitively like git interpret-trailers does
+fn commit_trailer(message: &str, key: &str) -> Option<String> {
+    let (_, trailers) = message.trim_end().rsplit_once("\n\n")?;
+    trailers.lines().rev().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name.trim().eq_ignore_ascii_case(key) {
+            Some(value.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+fn trailer_parent(repo: &Repository, record: &BranchRecord, key: &str) -> Option<String> {
+    let commit = repo
+        .find_commit(Oid::from_str(&record.commit_sha).ok()?)
+        .ok()?;
+    commit_trailer(&String::from_utf8_lossy(commit.message_bytes()), key)
+}
+
+// The config key wins over the trailer. Branches with neither keep no parent.
+pub fn populate_parents(repo: &Repository, records: &mut [BranchRecord], source: &ParentSource) {
+    let config = repo.config().and_then(|mut config| config.snapshot()).ok();
+    for record in records.iter_mut() {
+        let key = source.config_key.replace("{}", &record.name);
+        let parent = config
+            .as_ref()
+            .and_then(|config| config.get_string(&key).ok())
+            .or_else(|| trailer_parent(repo, record, source.trailer.as_deref()?));
+        record.parent = parent
+            .map(|p| p.strip_prefix("refs/heads/").unwrap_or(&p).to_string())
+            .filter(|p| !p.is_empty());
+    }
+}
+
+// Branch a stash was made on, from messages like "WIP on main: 1234abc subject" or
+// "On main: message"
+fn stash_branch_name(message: &str) -> Option<&str> {
+    let rest = message
+        .strip_prefix("WIP on ")
+        .or_else(|| message.strip_prefix("On "))?;
+    let (name, _) = rest.split_once(": ")?;
+    Some(name)
 }
 
-pub fn checkout_branch(repo: &Repository, record: &BranchRecord) -> Result<(), git2::Error> {
-    let treeish = repo.revparse_single(record.commit_sha.as_str())?;
-    repo.checkout_tree(&treeish, None)?;
-    repo.set_head(record.ref_name.as_str())?;
+// The stash reflog lists the newest stash first, so the entry index is N in stash@{N}
+pub fn populate_stashes(repo: &Repository, records: &mut [BranchRecord]) {
+    let reflog = match repo.reflog("refs/stash") {
+        Ok(reflog) => reflog,
+        Err(_) => return,
+    };
+    let mut newest = HashMap::new();
+    for (index, entry) in reflog.iter().enumerate() {
+        if let Some(name) = entry.message().and_then(stash_branch_name) {
+            newest.entry(name.to_string()).or_insert(index);
+        }
+    }
+    for record in records.iter_mut() {
+        record.stash_index = newest.get(&record.name).copied();
+    }
+}
+
+// Stash operations need a mutable repository. The copy keeps any work tree set from
+// GIT_WORK_TREE, which opening the repository again would lose.
+fn reopen(repo: &Repository) -> Result<Repository, Error> {
+    let copy = Repository::open(repo.path())?;
+    if let Some(workdir) = repo.workdir() {
+        copy.set_workdir(workdir, false)?;
+    }
+    Ok(copy)
+}
+
+// Applies stash@{index} to the working tree, keeping the stash itself
+pub fn apply_stash(repo: &Repository, index: usize) -> Result<(), Error> {
+    let mut repo = reopen(repo)?;
+    repo.stash_apply(index, None)?;
     Ok(())
 }
+
+// Whether tracked files have changes, staged or not. Untracked files are left out,
+// as stashing skips them too.
+pub fn has_local_changes(repo: &Repository) -> bool {
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).exclude_submodules(true);
+    repo.statuses(Some(&mut options))
+        .map(|statuses| !statuses.is_empty())
+        .unwrap_or(false)
+}
+
+// Stashes the changes to tracked files as stash@{0}, like `git stash push -m`
+pub fn stash_changes(repo: &Repository, message: &str) -> Result<(), Error> {
+    let mut repo = reopen(repo)?;
+    // Without user.name and user.email configured, the stash is still worth making
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now("git-checkout-recent", "git-checkout-recent"))?;
+    repo.stash_save(&signature, message, None)?;
+    Ok(())
+}
+
+// Applies stash@{0} and drops it. Returns false if the changes conflicted, in which
+// case the stash is kept like `git stash pop` does.
+pub fn pop_stash(repo: &Repository) -> Result<bool, Error> {
+    let mut repo = reopen(repo)?;
+    repo.stash_apply(0, None)?;
+    if repo.index()?.has_conflicts() {
+        return Ok(false);
+    }
+    repo.stash_drop(0)?;
+    Ok(true)
+}
+
+// The local branch other branches are compared against: the first of what origin/HEAD
+// points at, init.defaultBranch, main and master that exists locally
+pub fn default_base_branch(repo: &Repository) -> Option<String> {
+    let exists = |name: &String| repo.find_branch(name, BranchType::Local).is_ok();
+    vec![
+        origin_head_branch(repo),
+        init_default_branch(repo),
+        Some(String::from("main")),
+        Some(String::from("master")),
+    ]
+    .into_iter()
+    .flatten()
+    .find(exists)
+}
+
+// The commit that unmerged work is measured against, falling back to HEAD when there
+// is no base branch
+fn base_branch_oid(repo: &Repository) -> Option<Oid> {
+    default_base_branch(repo)
+        .and_then(|name| repo.find_branch(&name, BranchType::Local).ok())
+        .and_then(|branch| branch.get().target())
+        .or_else(|| repo.head().ok()?.target())
+}
+
+pub fn populate_unmerged_commits(repo: &Repository, records: &mut [BranchRecord]) {
+    let base = match base_branch_oid(repo) {
+        Some(base) => base,
+        None => return,
+    };
+    for record in records.iter_mut() {
+        if let Ok(tip) = Oid::from_str(record.commit_sha.as_str()) {
+            record.unmerged_commits = repo.graph_ahead_behind(tip, base).ok().map(|(a, _)| a);
+        }
+    }
+}
+
+fn ahead_behind(repo: &Repository, record: &BranchRecord) -> Option<(usize, usize)> {
+    let tip = Oid::from_str(&record.commit_sha).ok()?;
+    let upstream = Oid::from_str(record.upstream_sha.as_ref()?).ok()?;
+    repo.graph_ahead_behind(tip, upstream).ok()
+}
+
+pub fn populate_ahead_behind(repo: &Repository, records: &mut [BranchRecord]) {
+    for record in records.iter_mut() {
+        record.ahead_behind = ahead_behind(repo, record);
+    }
+}
+
+// Scores decay by half over this many days
+const FRECENCY_HALF_LIFE_DAYS: f64 = 7.0;
+
+fn decay(age_seconds: i64) -> f64 {
+    let age_days = age_seconds.max(0) as f64 / (24.0 * 60.0 * 60.0);
+    0.5f64.powf(age_days / FRECENCY_HALF_LIFE_DAYS)
+}
+
+// Scores each branch by how recently it was committed to plus how often and how
+// recently it was checked out according to the HEAD reflog
+pub fn populate_frecency(
+    repo: &Repository,
+    records: &mut [BranchRecord],
+    weights: &FrecencyWeights,
+) {
+    let now = Utc::now().timestamp();
+    let mut checkouts: Vec<(String, i64)> = vec![];
+    if let Ok(reflog) = repo.reflog("HEAD") {
+        for entry in reflog.iter() {
+            let to = entry
+                .message()
+                .and_then(|m| m.strip_prefix("checkout: moving from "))
+                .and_then(|m| m.split_once(" to "))
+                .map(|(_, to)| to.to_string());
+            if let Some(to) = to {
+                checkouts.push((to, entry.committer().when().seconds()));
+            }
+        }
+    }
+    for record in records.iter_mut() {
+        let checkout_score: f64 = checkouts
+            .iter()
+            .filter(|(name, _)| *name == record.name)
+            .map(|(_, time)| decay(now - time))
+            .sum();
+        record.last_checkout_seconds = checkouts
+            .iter()
+            .filter(|(name, _)| *name == record.name)
+            .map(|(_, time)| *time)
+            .max();
+        let commit_score = decay(now - record.time_seconds);
+        record.frecency = Some(weights.commit * commit_score + weights.checkout * checkout_score);
+    }
+}
+
+pub fn checkout_branch(repo: &Repository, record: &BranchRecord) -> Result<(), Error> {
+    let old_head = repo
+        .head()
+        .ok()
+        .and_then(|head| head.target())
+        .unwrap_or_else(Oid::zero);
+    let mut treeish = repo.revparse_single(record.commit_sha.as_str())?;
+    // Fail before touching the working tree if the branch ref can't be resolved
+    repo.find_reference(record.ref_name.as_str())?;
+
+    // Refs outside refs/heads, such as pull request heads, get a local branch first.
+    // Remote-tracking branches get one of the same name that tracks them, or reuse
+    // one that already does. This happens before the working tree is touched so
+    // that only moving HEAD can fail after it.
+    let mut created = None;
+    let ref_name = if record.ref_name.starts_with("refs/heads/") {
+        record.ref_name.clone()
+    } else if let Some(local_name) = remote_branch_local_name(repo, &record.ref_name) {
+        match repo.find_branch(&local_name, BranchType::Local) {
+            Ok(existing) => {
+                let upstream = existing.upstream().ok();
+                let tracks = upstream
+                    .as_ref()
+                    .map(|upstream| upstream.get().name_bytes());
+                if tracks != Some(record.ref_name.as_bytes()) {
+                    return Err(Error::TracksOtherBranch {
+                        branch: local_name,
+                        upstream: record.name.clone(),
+                    });
+                }
+                treeish = existing.get().peel_to_commit()?.into_object();
+                String::from_utf8_lossy(existing.get().name_bytes()).into_owned()
+            }
+            Err(_) => {
+                let commit = treeish.peel_to_commit()?;
+                let mut branch = repo.branch(&local_name, &commit, false)?;
+                branch.set_upstream(Some(&record.name))?;
+                let name = String::from_utf8_lossy(branch.get().name_bytes()).into_owned();
+                created = Some(branch);
+                name
+            }
+        }
+    } else {
+        let commit = treeish.peel_to_commit()?;
+        let branch = repo.branch(&record.name, &commit, false)?;
+        let name = String::from_utf8_lossy(branch.get().name_bytes()).into_owned();
+        created = Some(branch);
+        name
+    };
+    // A branch made just for this switch goes again if the switch doesn't happen
+    let discard_created = |created: Option<Branch>| {
+        if let Some(mut branch) = created {
+            let _ = branch.delete();
+        }
+    };
+
+    // Spelled out rather than left to libgit2's default: uncommitted changes in the
+    // way make the checkout fail instead of being overwritten
+    if let Err(e) = repo.checkout_tree(&treeish, Some(CheckoutBuilder::new().safe())) {
+        discard_created(created);
+        return Err(Error::from(e));
+    }
+
+    if let Err(e) = repo.set_head(&ref_name) {
+        discard_created(created);
+        // HEAD did not move, so bring the working tree back in line with it. A safe
+        // checkout would compare against HEAD and leave everything as it is, so force
+        // just the paths the checkout above changed.
+        let rollback = repo
+            .head()
+            .and_then(|head| head.peel_to_tree())
+            .and_then(|head_tree| {
+                let diff =
+                    repo.diff_tree_to_tree(Some(&head_tree), Some(&treeish.peel_to_tree()?), None)?;
+                let mut checkout = CheckoutBuilder::new();
+                checkout.force();
+                for delta in diff.deltas() {
+                    for file in [delta.old_file(), delta.new_file()] {
+                        if let Some(path) = file.path() {
+                            checkout.path(path);
+                        }
+                    }
+                }
+                if diff.deltas().len() > 0 {
+                    repo.checkout_tree(head_tree.as_object(), Some(&mut checkout))?;
+                }
+                Ok(())
+            });
+        return Err(match rollback {
+            Ok(()) => Error::from(e),
+            Err(_) => Error::InconsistentHead {
+                head: head_shorthand(repo).unwrap_or_else(|| String::from("HEAD")),
+                target: record.name.clone(),
+                source: e,
+            },
+        });
+    }
+
+    run_post_checkout_hook(repo, old_head, treeish.id());
+    Ok(())
+}
+
+// "refs/remotes/origin/feature/x" is checked out as "feature/x"
+fn remote_branch_local_name(repo: &Repository, ref_name: &str) -> Option<String> {
+    let rest = ref_name.strip_prefix("refs/remotes/")?;
+    // Remote names can contain slashes, so the longest matching remote wins
+    let remotes = repo.remotes().ok()?;
+    remotes
+        .iter()
+        .flatten()
+        .filter_map(|remote| rest.strip_prefix(remote)?.strip_prefix('/'))
+        .min_by_key(|name| name.len())
+        .map(String::from)
+}
+
+// Paths of submodules whose recorded commit differs between the two commits' trees
+pub fn changed_submodules(repo: &Repository, old: Oid, new: Oid) -> Vec<String> {
+    let tree = |oid: Oid| repo.find_commit(oid).and_then(|commit| commit.tree()).ok();
+    let (old_tree, new_tree) = match (tree(old), tree(new)) {
+        (Some(old_tree), Some(new_tree)) => (old_tree, new_tree),
+        _ => return vec![],
+    };
+    let diff = match repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None) {
+        Ok(diff) => diff,
+        Err(_) => return vec![],
+    };
+    diff.deltas()
+        .filter(|delta| {
+            delta.old_file().mode() == FileMode::Commit
+                || delta.new_file().mode() == FileMode::Commit
+        })
+        .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+// Files a switch from HEAD to the branch touches, by kind of change
+pub struct SwitchImpact {
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+}
+
+impl SwitchImpact {
+    pub fn total(&self) -> usize {
+        self.added + self.removed + self.modified
+    }
+}
+
+// What checking out the branch would change, going by the committed trees only
+pub fn switch_impact(repo: &Repository, record: &BranchRecord) -> Result<SwitchImpact, Error> {
+    let old_tree = repo.head()?.peel_to_tree()?;
+    let new_tree = repo
+        .find_commit(Oid::from_str(&record.commit_sha)?)?
+        .tree()?;
+    let diff = repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+    let mut impact = SwitchImpact {
+        added: 0,
+        removed: 0,
+        modified: 0,
+    };
+    for delta in diff.deltas() {
+        match delta.status() {
+            Delta::Added => impact.added += 1,
+            Delta::Deleted => impact.removed += 1,
+            _ => impact.modified += 1,
+        }
+    }
+    Ok(impact)
+}
+
+pub fn update_submodule(repo: &Repository, path: &str) -> Result<(), git2::Error> {
+    repo.find_submodule(path)?.update(true, None)
+}
+
+// The commit in `git describe --tags` form, e.g. "v1.2.0-3-g1a2b3c4", or None when
+// no tag is reachable from it
+pub fn describe_commit(repo: &Repository, sha: &str) -> Option<String> {
+    let object = repo.revparse_single(sha).ok()?;
+    let describe = object
+        .describe(DescribeOptions::new().describe_tags())
+        .ok()?;
+    describe.format(Some(&DescribeFormatOptions::new())).ok()
+}
+
+pub fn is_protected(name: &str, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| matches_glob(name, pattern))
+}
+
+// Deletes a local branch whether or not it was merged, like `git branch -D`, unless
+// it matches one of the protected patterns
+pub fn delete_branch(
+    repo: &Repository,
+    record: &BranchRecord,
+    protected: &[String],
+) -> Result<(), Error> {
+    let name = match record.ref_name.strip_prefix("refs/heads/") {
+        Some(name) => name,
+        None => return Err(Error::NotABranch(record.name.clone())),
+    };
+    if is_protected(name, protected) {
+        return Err(Error::ProtectedBranch(record.name.clone()));
+    }
+    let mut branch = repo.find_branch(name, BranchType::Local)?;
+    if branch.is_head() {
+        return Err(Error::BranchCheckedOut(record.name.clone()));
+    }
+    branch.delete()?;
+    Ok(())
+}
+
+// Stores the branch description the way `git branch --edit-description` does. An
+// empty description removes it.
+pub fn set_branch_description(
+    repo: &Repository,
+    record: &BranchRecord,
+    description: &str,
+) -> Result<(), Error> {
+    let name = match record.ref_name.strip_prefix("refs/heads/") {
+        Some(name) => name,
+        None => return Err(Error::NotABranch(record.name.clone())),
+    };
+    let key = format!("branch.{name}.description");
+    let mut config = repo.config()?.open_level(ConfigLevel::Local)?;
+    if description.is_empty() {
+        match config.remove(&key) {
+            Err(e) if e.code() != ErrorCode::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    } else {
+        config.set_str(&key, description)?;
+    }
+    Ok(())
+}
+
+// When anything was last fetched, going by the modification time of FETCH_HEAD
+pub fn last_fetch_time(repo: &Repository) -> Option<i64> {
+    let modified = fs::metadata(repo.path().join("FETCH_HEAD"))
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
+}
+
+// Whether the commit carries a gpgsig header. The signature itself is not verified.
+pub fn is_commit_signed(repo: &Repository, oid: Oid) -> bool {
+    repo.extract_signature(&oid, None).is_ok()
+}
+
+pub fn resolve_commit(repo: &Repository, spec: &str) -> Result<Oid, git2::Error> {
+    let commit = repo.revparse_single(spec)?.peel_to_commit()?;
+    Ok(commit.id())
+}
+
+// Resolves a revision such as "main@{yesterday}" or "@{-1}" to its commit, along with
+// the local branch it names, if it names one
+pub fn resolve_revision(
+    repo: &Repository,
+    spec: &str,
+) -> Result<(Oid, Option<BranchRecord>), git2::Error> {
+    let (object, reference) = repo.revparse_ext(spec)?;
+    let commit = object.peel_to_commit()?.id();
+    let branch = reference
+        .filter(|reference| reference.is_branch())
+        .and_then(|reference| {
+            parse_local_branch(repo, &Branch::wrap(reference), &BranchContext::new(repo))
+        });
+    Ok((commit, branch))
+}
+
+// Checks out the commit and leaves HEAD detached at it
+pub fn checkout_detached(repo: &Repository, commit: Oid) -> Result<(), Error> {
+    let old_head = repo
+        .head()
+        .ok()
+        .and_then(|head| head.target())
+        .unwrap_or_else(Oid::zero);
+    let treeish = repo.find_object(commit, None)?;
+    repo.checkout_tree(&treeish, Some(CheckoutBuilder::new().safe()))?;
+    repo.set_head_detached(commit)?;
+    run_post_checkout_hook(repo, old_head, commit);
+    Ok(())
+}
+
+// Shell-style: "*" matches any run of characters, slashes included as with
+// `git branch --list`, and "?" matches a single character
+pub fn matches_glob(name: &str, pattern: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut n, mut p) = (0, 0);
+    // Where the last "*" was seen and how much of the name it has taken so far
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                n += 1;
+                p += 1;
+            }
+            _ => match star {
+                // Let the "*" take one more character and try again
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Matches like `git log --committer`: a case-insensitive search in "name <email>"
+pub fn committer_matches(record: &BranchRecord, pattern: &str) -> bool {
+    let identity = format!("{} <{}>", record.committer_name, record.committer_email);
+    identity.to_lowercase().contains(&pattern.to_lowercase())
+}
+
+pub fn branch_contains(repo: &Repository, record: &BranchRecord, commit: Oid) -> bool {
+    match Oid::from_str(record.commit_sha.as_str()) {
+        Ok(tip) => tip == commit || repo.graph_descendant_of(tip, commit).unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+// Whether the branch's tip is already part of `target`, as with `git branch --merged`
+pub fn is_merged_into(repo: &Repository, record: &BranchRecord, target: Oid) -> bool {
+    match Oid::from_str(record.commit_sha.as_str()) {
+        Ok(tip) => tip == target || repo.graph_descendant_of(target, tip).unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+pub enum PullOutcome {
+    NoUpstream,
+    UpToDate,
+    FastForwarded(usize),
+    Diverged,
+}
+
+fn fetch_upstream(repo: &Repository, record: &BranchRecord) -> Result<(), Error> {
+    let remote_name = repo.branch_upstream_remote(record.ref_name.as_str())?;
+    let mut remote = repo.find_remote(remote_name.as_str().unwrap_or("origin"))?;
+
+    let config = repo.config()?;
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|url, username, allowed| {
+        if allowed.is_ssh_key() {
+            Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else {
+            Cred::credential_helper(&config, url, username)
+        }
+    });
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+
+    // An empty refspec list fetches using the remote's configured refspecs
+    let refspecs: &[&str] = &[];
+    remote.fetch(refspecs, Some(&mut fetch_options), None)?;
+    Ok(())
+}
+
+pub fn pull_fast_forward(repo: &Repository, record: &BranchRecord) -> Result<PullOutcome, Error> {
+    let branch = repo.find_branch(record.name.as_str(), BranchType::Local)?;
+    if branch.upstream().is_err() {
+        return Ok(PullOutcome::NoUpstream);
+    }
+
+    fetch_upstream(repo, record)?;
+
+    let upstream_oid = match branch.upstream()?.get().target() {
+        Some(oid) => oid,
+        None => return Ok(PullOutcome::NoUpstream),
+    };
+    let annotated = repo.find_annotated_commit(upstream_oid)?;
+    let (analysis, _) = repo.merge_analysis(&[&annotated])?;
+
+    if analysis.is_up_to_date() {
+        return Ok(PullOutcome::UpToDate);
+    }
+    if !analysis.is_fast_forward() {
+        return Ok(PullOutcome::Diverged);
+    }
+
+    let local_oid = Oid::from_str(record.commit_sha.as_str())?;
+    let (new_commits, _) = repo.graph_ahead_behind(upstream_oid, local_oid)?;
+    apply_fast_forward(repo, record, upstream_oid)?;
+    Ok(PullOutcome::FastForwarded(new_commits))
+}
+
+// Moves the checked out branch to `target`, updating the working tree first so a
+// conflict leaves the branch ref untouched
+fn apply_fast_forward(repo: &Repository, record: &BranchRecord, target: Oid) -> Result<(), Error> {
+    let object = repo.find_object(target, None)?;
+    repo.checkout_tree(&object, Some(CheckoutBuilder::new().safe()))?;
+    let mut reference = repo.find_reference(record.ref_name.as_str())?;
+    reference.set_target(target, "checkout-recent: fast-forward")?;
+    Ok(())
+}
+
+// Like pull_fast_forward, but only against the upstream as last fetched, without
+// touching the network
+pub fn fast_forward_if_behind(
+    repo: &Repository,
+    record: &BranchRecord,
+) -> Result<PullOutcome, Error> {
+    let branch = repo.find_branch(record.name.as_str(), BranchType::Local)?;
+    let upstream_oid = match branch.upstream().ok().and_then(|u| u.get().target()) {
+        Some(oid) => oid,
+        None => return Ok(PullOutcome::NoUpstream),
+    };
+    let local_oid = Oid::from_str(record.commit_sha.as_str())?;
+    match repo.graph_ahead_behind(local_oid, upstream_oid)? {
+        (_, 0) => Ok(PullOutcome::UpToDate),
+        (0, behind) => {
+            apply_fast_forward(repo, record, upstream_oid)?;
+            Ok(PullOutcome::FastForwarded(behind))
+        }
+        _ => Ok(PullOutcome::Diverged),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRepo;
+
+    #[test]
+    fn relative_times_are_measured_from_the_given_now() {
+        let now = Utc.timestamp(1_600_000_000, 0);
+        let ago = |seconds: i64| format_relative_time(1_600_000_000 - seconds, 0, now);
+        assert_eq!(ago(2 * 60 * 60), "2 hours ago");
+        assert_eq!(ago(3 * 24 * 60 * 60), "3 days ago");
+        // The commit's time zone doesn't change how long ago it was
+        assert_eq!(
+            format_relative_time(1_600_000_000 - 2 * 60 * 60, 330, now),
+            "2 hours ago"
+        );
+    }
+
+    #[test]
+    fn out_of_range_offsets_fall_back_to_utc() {
+        assert_eq!(commit_offset(120), FixedOffset::east(2 * 60 * 60));
+        assert_eq!(commit_offset(-90), FixedOffset::west(90 * 60));
+        for bogus in [24 * 60, -24 * 60, 100_000, i32::MAX, i32::MIN] {
+            assert_eq!(commit_offset(bogus), FixedOffset::east(0));
+        }
+    }
+
+    #[test]
+    fn dates_with_out_of_range_offsets_still_format() {
+        let now = Utc.timestamp(1_600_000_000, 0);
+        let two_hours_ago = 1_600_000_000 - 2 * 60 * 60;
+        assert_eq!(
+            format_relative_time(two_hours_ago, i32::MAX, now),
+            "2 hours ago"
+        );
+
+        let mut record = crate::testing::record("a", "0", two_hours_ago);
+        record.offset_minutes = 100_000;
+        assert_eq!(record.format_date_absolute(), "2020-09-13 10:26");
+        record.offset_minutes = 60;
+        assert_eq!(record.format_date_absolute(), "2020-09-13 11:26");
+    }
+
+    fn record(test: &TestRepo, name: &str) -> BranchRecord {
+        extract_named_branches(&test.repo, &[name.to_string()]).remove(0)
+    }
+
+    #[test]
+    fn invalid_utf8_names_and_messages_are_kept_lossily() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let test = TestRepo::new();
+        let head = test.commit("f", "a");
+        let tree = test.repo.find_commit(head).unwrap().tree_id();
+        // Signatures and messages in a legacy encoding, which git stores as is
+        let mut raw = format!("tree {tree}\nparent {head}\n").into_bytes();
+        raw.extend_from_slice(b"author Jos\xe9 <jose@example.com> 1600000000 +0000\n");
+        raw.extend_from_slice(b"committer Jos\xe9 <jose@example.com> 1600000000 +0000\n");
+        raw.extend_from_slice(b"\nCaf\xe9 fix\n");
+        let oid = test
+            .repo
+            .odb()
+            .unwrap()
+            .write(git2::ObjectType::Commit, &raw)
+            .unwrap();
+        let ref_path = test.repo.path().join("refs/heads");
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9");
+        fs::write(ref_path.join(name), format!("{oid}\n")).unwrap();
+
+        let mut records = extract_local_branches(&test.repo);
+        records.retain(|r| r.commit_sha == oid.to_string());
+        assert_eq!(records.len(), 1);
+        let record = &mut records[0];
+        assert_eq!(record.name, "caf\u{fffd}");
+        load_details(&test.repo, record);
+        assert_eq!(record.author_name, "Jos\u{fffd}");
+        assert_eq!(record.summary, "Caf\u{fffd} fix");
+    }
+
+    #[test]
+    fn current_branch_is_found_when_it_is_only_in_packed_refs() {
+        let test = TestRepo::new();
+        let base = test.commit("f", "a");
+        let head = test.commit("f", "b");
+        test.branch("other", base);
+        // What `git pack-refs --all` leaves behind
+        let git_dir = test.repo.path();
+        fs::write(
+            git_dir.join("packed-refs"),
+            format!(
+                "# pack-refs with: peeled fully-peeled sorted \n\
+                 {head} refs/heads/master\n{base} refs/heads/other\n"
+            ),
+        )
+        .unwrap();
+        fs::remove_file(git_dir.join("refs/heads/master")).unwrap();
+        fs::remove_file(git_dir.join("refs/heads/other")).unwrap();
+
+        let records = extract_local_branches(&test.repo);
+        let current: Vec<&str> = records
+            .iter()
+            .filter(|r| r.is_current_branch)
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(current, vec!["master"]);
+        assert_eq!(records.len(), 2);
+    }
+
+    #[test]
+    fn current_branch_falls_back_to_the_head_commit() {
+        let test = TestRepo::new();
+        let base = test.commit("f", "a");
+        let head = test.commit("f", "b");
+        test.branch("other", base);
+        // As if the name HEAD points at could not be read
+        let context = BranchContext {
+            head_branch_refname: None,
+            head_oid: Some(head),
+            match_head_oid: true,
+            default_refname: None,
+            previous_refname: None,
+        };
+        let is_current = |name| {
+            let branch = test.repo.find_branch(name, BranchType::Local).unwrap();
+            parse_local_branch(&test.repo, &branch, &context)
+                .unwrap()
+                .is_current_branch
+        };
+        assert!(is_current("master"));
+        assert!(!is_current("other"));
+    }
+
+    #[test]
+    fn checkout_branch_keeps_conflicting_local_changes() {
+        let test = TestRepo::new();
+        test.commit("f", "a");
+        let oid = test.commit("f", "b");
+        test.branch("other", oid);
+        test.repo
+            .reset(
+                &test.repo.revparse_single("HEAD~1").unwrap(),
+                git2::ResetType::Hard,
+                None,
+            )
+            .unwrap();
+        test.write("f", "local change");
+
+        let result = checkout_branch(&test.repo, &record(&test, "other"));
+        assert!(matches!(result, Err(Error::CheckoutConflict(_))));
+        assert_eq!(test.read("f"), "local change");
+        assert_eq!(head_shorthand(&test.repo).as_deref(), Some("master"));
+    }
+
+    fn stash_messages(repo: &Repository) -> Vec<String> {
+        let mut messages = vec![];
+        reopen(repo)
+            .unwrap()
+            .stash_foreach(|_, message, _| {
+                messages.push(message.to_string());
+                true
+            })
+            .unwrap();
+        messages
+    }
+
+    #[test]
+    fn autostash_keeps_the_stash_after_switching() {
+        let test = TestRepo::new();
+        test.commit("f", "a");
+        let oid = test.commit("g", "other");
+        test.branch("other", oid);
+        test.write("f", "local change");
+
+        assert!(has_local_changes(&test.repo));
+        stash_changes(&test.repo, "autostash before switching to other").unwrap();
+        assert!(!has_local_changes(&test.repo));
+        checkout_branch(&test.repo, &record(&test, "other")).unwrap();
+
+        assert_eq!(
+            stash_messages(&test.repo),
+            vec!["On master: autostash before switching to other"]
+        );
+    }
+
+    #[test]
+    fn pop_stash_keeps_the_stash_when_it_conflicts() {
+        let test = TestRepo::new();
+        let base = test.commit("f", "a");
+        let oid = test.commit("f", "b");
+        test.branch("other", oid);
+        test.repo
+            .reset(
+                &test.repo.find_object(base, None).unwrap(),
+                git2::ResetType::Hard,
+                None,
+            )
+            .unwrap();
+        test.write("f", "local change");
+        stash_changes(&test.repo, "autostash").unwrap();
+        checkout_branch(&test.repo, &record(&test, "other")).unwrap();
+
+        assert!(!matches!(pop_stash(&test.repo), Ok(true)));
+        assert_eq!(stash_messages(&test.repo).len(), 1);
+    }
+
+    #[test]
+    fn stashing_uses_a_work_tree_set_on_the_repository() {
+        let test = TestRepo::new();
+        test.commit("f", "a");
+        // A second checkout of the same commit, standing in for GIT_WORK_TREE
+        let other = TestRepo::new();
+        let repo = Repository::open(test.repo.path()).unwrap();
+        repo.set_workdir(other.workdir(), false).unwrap();
+        repo.checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        other.write("f", "changed elsewhere");
+
+        stash_changes(&repo, "test").unwrap();
+        assert_eq!(other.read("f"), "a");
+        assert_eq!(test.read("f"), "a");
+        assert!(pop_stash(&repo).unwrap());
+        assert_eq!(other.read("f"), "changed elsewhere");
+    }
+
+    #[test]
+    fn is_merged_into_tells_merged_and_unmerged_branches_apart() {
+        let test = TestRepo::new();
+        let base = test.commit("f", "a");
+        test.branch("merged", base);
+        let head = test.commit("f", "b");
+        test.branch("at-head", head);
+        let side = test.repo.find_commit(base).unwrap();
+        test.repo.branch("unmerged", &side, false).unwrap();
+        test.repo.set_head("refs/heads/unmerged").unwrap();
+        test.commit("g", "side");
+        test.repo.set_head("refs/heads/master").unwrap();
+
+        assert!(is_merged_into(&test.repo, &record(&test, "merged"), head));
+        assert!(is_merged_into(&test.repo, &record(&test, "at-head"), head));
+        assert!(!is_merged_into(
+            &test.repo,
+            &record(&test, "unmerged"),
+            head
+        ));
+    }
+
+    #[test]
+    fn matches_glob_handles_stars_and_question_marks() {
+        assert!(matches_glob("main", "main"));
+        assert!(!matches_glob("main", "mai"));
+        assert!(!matches_glob("mai", "main"));
+        assert!(matches_glob("feature/x", "feature/*"));
+        assert!(matches_glob("feature/deep/x", "feature/*"));
+        assert!(!matches_glob("bugfix/x", "feature/*"));
+        assert!(matches_glob("release/1.2", "release/?.?"));
+        assert!(!matches_glob("release/1.22", "release/?.?"));
+        assert!(matches_glob("a-fix-b-fix", "*fix"));
+        assert!(matches_glob("a-fix-b", "*fix*"));
+        assert!(matches_glob("", "*"));
+        assert!(!matches_glob("", "?"));
+        assert!(matches_glob("naïve", "na?ve"));
+    }
+
+    #[test]
+    fn delete_branch_refuses_protected_branches() {
+        let test = TestRepo::new();
+        let oid = test.commit("f", "a");
+        test.branch("release/1.0", oid);
+        test.branch("feature", oid);
+        let protected = vec![String::from("release/*")];
+
+        let result = delete_branch(&test.repo, &record(&test, "release/1.0"), &protected);
+        assert!(matches!(result, Err(Error::ProtectedBranch(_))));
+        delete_branch(&test.repo, &record(&test, "feature"), &protected).unwrap();
+
+        assert_eq!(test.branch_names(), vec!["master", "release/1.0"]);
+    }
+
+    // Leaves only the given local branches, with HEAD on the first of them
+    fn keep_branches(test: &TestRepo, names: &[&str], oid: Oid) {
+        for name in names {
+            test.branch(name, oid);
+        }
+        test.repo
+            .set_head(&format!("refs/heads/{}", names[0]))
+            .unwrap();
+        let mut master = test.repo.find_branch("master", BranchType::Local).unwrap();
+        master.delete().unwrap();
+    }
+
+    fn set_origin_head(test: &TestRepo, name: &str, oid: Oid) {
+        let target = format!("refs/remotes/origin/{name}");
+        test.repo.reference(&target, oid, false, "test").unwrap();
+        test.repo
+            .reference_symbolic("refs/remotes/origin/HEAD", &target, false, "test")
+            .unwrap();
+    }
+
+    #[test]
+    fn base_branch_follows_origin_head() {
+        let test = TestRepo::new();
+        let oid = test.commit("f", "a");
+        test.branch("develop", oid);
+        test.branch("main", oid);
+        set_origin_head(&test, "develop", oid);
+        assert_eq!(default_base_branch(&test.repo).as_deref(), Some("develop"));
+    }
+
+    #[test]
+    fn base_branch_falls_back_to_init_default_branch() {
+        let test = TestRepo::new();
+        let oid = test.commit("f", "a");
+        test.branch("trunk", oid);
+        test.repo
+            .config()
+            .unwrap()
+            .set_str("init.defaultBranch", "trunk")
+            .unwrap();
+        assert_eq!(default_base_branch(&test.repo).as_deref(), Some("trunk"));
+
+        // Also when origin/HEAD names a branch that has no local copy
+        set_origin_head(&test, "develop", oid);
+        assert_eq!(default_base_branch(&test.repo).as_deref(), Some("trunk"));
+    }
+
+    #[test]
+    fn base_branch_falls_back_to_main_then_master() {
+        let test = TestRepo::new();
+        let oid = test.commit("f", "a");
+        assert_eq!(default_base_branch(&test.repo).as_deref(), Some("master"));
+        test.branch("main", oid);
+        assert_eq!(default_base_branch(&test.repo).as_deref(), Some("main"));
+
+        let test = TestRepo::new();
+        let oid = test.commit("f", "a");
+        keep_branches(&test, &["main"], oid);
+        assert_eq!(default_base_branch(&test.repo).as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn base_branch_is_none_without_a_candidate() {
+        let test = TestRepo::new();
+        let oid = test.commit("f", "a");
+        keep_branches(&test, &["feature"], oid);
+        assert_eq!(default_base_branch(&test.repo), None);
+    }
+
+    #[test]
+    fn a_detached_head_is_remembered_by_its_commit() {
+        let test = TestRepo::new();
+        let first = test.commit("f", "a");
+        test.commit("f", "b");
+        assert_eq!(head_position(&test.repo).as_deref(), Some("master"));
+
+        checkout_detached(&test.repo, first).unwrap();
+        let position = head_position(&test.repo).unwrap();
+        assert_eq!(position, first.to_string());
+
+        // Going back there after switching away lands on the same commit
+        checkout_branch(&test.repo, &record(&test, "master")).unwrap();
+        let (commit, _) = resolve_revision(&test.repo, &position).unwrap();
+        checkout_detached(&test.repo, commit).unwrap();
+        assert_eq!(test.repo.refname_to_id("HEAD").unwrap(), first);
+        assert!(test.repo.head_detached().unwrap());
+    }
+}
@@ -0,0 +1,49 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use chrono::offset::Utc;
+use git2::Repository;
+
+const HISTORY_FILE: &str = "checkout-recent.log";
+const SELECTION_FILE: &str = "checkout-recent-selection";
+
+fn history_path(repo: &Repository) -> PathBuf {
+    repo.path().join(HISTORY_FILE)
+}
+
+// Appends a "timestamp<TAB>from<TAB>to" line. Failures are ignored so logging never
+// gets in the way of the checkout itself.
+pub fn record_checkout(repo: &Repository, from: &str, to: &str) {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path(repo));
+    if let Ok(mut file) = file {
+        let _ = writeln!(file, "{}\t{}\t{}", Utc::now().to_rfc3339(), from, to);
+    }
+}
+
+// The (from, to) branches of the most recent checkout made by this tool
+pub fn last_checkout(repo: &Repository) -> Option<(String, String)> {
+    let contents = fs::read_to_string(history_path(repo)).ok()?;
+    let line = contents.lines().last()?;
+    let mut fields = line.split('\t').skip(1);
+    let from = fields.next()?.to_string();
+    let to = fields.next()?.to_string();
+    Some((from, to))
+}
+
+// Keeps the name of the branch chosen in the picker, to highlight it next time.
+// Like the history, failing to write it is ignored.
+pub fn record_selection(repo: &Repository, name: &str) {
+    let _ = fs::write(repo.path().join(SELECTION_FILE), format!("{name}\n"));
+}
+
+// The branch chosen in the picker last time, if the file is there and names one
+pub fn last_selection(repo: &Repository) -> Option<String> {
+    let contents = fs::read_to_string(repo.path().join(SELECTION_FILE)).ok()?;
+    let name = contents.lines().next()?.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
@@ -0,0 +1,101 @@
+pub mod cli;
+pub mod config;
+pub mod error;
+pub mod git;
+pub mod history;
+pub mod pipeline;
+pub mod preview;
+pub mod profile;
+#[cfg(test)]
+mod testing;
+pub mod ui;
+mod util;
+
+use git2::Repository;
+
+use config::Config;
+use error::Error;
+use git::{
+    extract_local_branches, extract_remote_branches, last_fetch_time, load_details,
+    merge_remote_duplicates, sort_records, BranchRecord, SortKey,
+};
+use ui::{render_branch_selection, BranchTable, DisplayOptions, DEFAULT_COLUMNS};
+
+// Runs the interactive picker over the repository's local branches and returns the
+// chosen one without checking it out, leaving that decision to the caller. Nothing
+// is changed: deleting branches and editing descriptions are refused, and toggled
+// columns are not saved.
+pub fn select_recent_branch(repo: &Repository) -> Result<Option<BranchRecord>, Error> {
+    let config = Config::load(repo.workdir());
+    let load = |include_remotes| {
+        let mut records = extract_local_branches(repo);
+        if include_remotes {
+            records.extend(extract_remote_branches(repo));
+            merge_remote_duplicates(repo, &mut records);
+        }
+        sort_records(&mut records, SortKey::Recency);
+        records
+    };
+    let details = |record: &mut BranchRecord| load_details(repo, record);
+    let (options, set_description, delete) = read_only_picker(repo);
+    let mut table = BranchTable::new(load(false), &options);
+    let selected = render_branch_selection(
+        &mut table,
+        &config,
+        &load,
+        &details,
+        &set_description,
+        &delete,
+    )?;
+    Ok(selected.cloned())
+}
+
+type SetDescription = fn(&BranchRecord, &str) -> Result<(), Error>;
+type DeleteBranch = fn(&BranchRecord) -> Result<(), Error>;
+
+// The display options and branch actions select_recent_branch runs the picker with
+fn read_only_picker(repo: &Repository) -> (DisplayOptions, SetDescription, DeleteBranch) {
+    let options = DisplayOptions {
+        batch_size: 20,
+        columns: DEFAULT_COLUMNS.to_vec(),
+        last_fetch_seconds: last_fetch_time(repo),
+        save_columns: false,
+        ..DisplayOptions::default()
+    };
+    (options, refuse_description, refuse_delete)
+}
+
+fn refuse_description(_record: &BranchRecord, _description: &str) -> Result<(), Error> {
+    Err(Error::ReadOnly)
+}
+
+fn refuse_delete(_record: &BranchRecord) -> Result<(), Error> {
+    Err(Error::ReadOnly)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git::extract_named_branches;
+    use testing::TestRepo;
+
+    #[test]
+    fn picker_actions_leave_the_repo_unchanged() {
+        let test = TestRepo::new();
+        let oid = test.commit("f", "a");
+        test.branch("other", oid);
+        let record = extract_named_branches(&test.repo, &[String::from("other")]).remove(0);
+
+        let (options, set_description, delete) = read_only_picker(&test.repo);
+        assert!(!options.save_columns);
+        assert!(matches!(delete(&record), Err(Error::ReadOnly)));
+        assert!(matches!(
+            set_description(&record, "text"),
+            Err(Error::ReadOnly)
+        ));
+
+        assert_eq!(test.branch_names(), vec!["master", "other"]);
+        let config = test.repo.config().unwrap().snapshot().unwrap();
+        assert!(config.get_string("branch.other.description").is_err());
+    }
+}
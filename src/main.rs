@@ -1,55 +1,932 @@
-mod git;
-mod ui;
-mod util;
-
+use chrono::offset::Utc;
+use git2::ErrorCode;
+use git2::Oid;
 use git2::Repository;
 use git2::RepositoryState;
-use std::process::exit;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::process::{exit, Command, Stdio};
+use std::time::Duration;
+
+use git_checkout_recent::cli::{ExportFormat, Options};
+use git_checkout_recent::config::Config;
+use git_checkout_recent::error::Error;
+use git_checkout_recent::git::{
+    apply_stash, changed_submodules, checkout_branch, checkout_detached, create_branch,
+    create_tracking_branch, current_branch, default_remote, delete_branch, describe_commit,
+    extract_branches_with_prefix, extract_local_branches, extract_named_branches,
+    extract_namespace_refs, extract_remote_branches, fast_forward_if_behind, for_each_local_branch,
+    format_compact_age, has_local_changes, head_position, head_shorthand, is_commit_signed,
+    is_merged_into, last_fetch_time, load_details, merge_remote_duplicates, origin_web_url,
+    pop_stash, populate_frecency, populate_parents, populate_stashes, populate_worktrees,
+    previous_branch_name, pull_fast_forward, remotes_with_branch, resolve_commit, resolve_revision,
+    set_branch_description, sort_records, stash_changes, switch_impact, update_submodule,
+    BranchRecord, PullOutcome, SortKey,
+};
+use git_checkout_recent::history::{
+    last_checkout, last_selection, record_checkout, record_selection,
+};
+use git_checkout_recent::pipeline;
+use git_checkout_recent::profile;
+use git_checkout_recent::ui::{
+    parse_style, render_branch_selection, BranchTable, Column, DisplayOptions, DEFAULT_COLUMNS,
+    FLAG_ORDER,
+};
+
+// Returns how many commits the branch was fast-forwarded by
+fn pull_selected_branch(
+    repo: &Repository,
+    branch_record: &BranchRecord,
+) -> Result<Option<usize>, Error> {
+    match pull_fast_forward(repo, branch_record) {
+        Ok(PullOutcome::NoUpstream) => {
+            println!(
+                "Branch '{}' has no upstream, not pulling",
+                branch_record.name
+            );
+            Ok(None)
+        }
+        Ok(PullOutcome::UpToDate) => {
+            println!("Already up to date");
+            Ok(None)
+        }
+        Ok(PullOutcome::FastForwarded(n)) => {
+            println!("Fast-forwarded {n} commit(s)");
+            Ok(Some(n))
+        }
+        Ok(PullOutcome::Diverged) => Err(Error::Diverged(branch_record.name.clone())),
+        Err(e) => Err(Error::Pull(Box::new(e))),
+    }
+}
+
+// Quietly does nothing unless the branch is strictly behind its upstream
+fn fast_forward_selected_branch(repo: &Repository, branch_record: &BranchRecord) -> Option<usize> {
+    match fast_forward_if_behind(repo, branch_record) {
+        Ok(PullOutcome::FastForwarded(n)) => {
+            println!("Fast-forwarded {n} commit(s)");
+            return Some(n);
+        }
+        Ok(PullOutcome::Diverged) => println!(
+            "Not fast-forwarding '{}', it has diverged from its upstream",
+            branch_record.name
+        ),
+        Ok(_) => {}
+        Err(e) => println!("Failed to fast-forward: {e}"),
+    }
+    None
+}
 
-use git::{checkout_branch, extract_local_branches, BranchRecord};
-use ui::{render_branch_selection, BranchTable};
+// Runs --pull or --ff-if-behind, if given. A detached HEAD is left where it is.
+fn update_selected_branch(
+    repo: &Repository,
+    branch_record: &BranchRecord,
+    options: &Options,
+) -> Result<Option<usize>, Error> {
+    if options.detach {
+        Ok(None)
+    } else if options.pull {
+        pull_selected_branch(repo, branch_record)
+    } else if options.ff_if_behind {
+        Ok(fast_forward_selected_branch(repo, branch_record))
+    } else {
+        Ok(None)
+    }
+}
+
+// Shows a desktop notification through notify-send, or osascript on macOS, for
+// --notify. It is only a courtesy, so any failure is ignored.
+fn notify(message: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {message:?} with title \"git checkout-recent\""
+        ));
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.arg("git checkout-recent").arg(message);
+        command
+    };
+    let _ = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+fn notify_switch(summary: String, forwarded: Option<usize>) {
+    match forwarded {
+        Some(n) => notify(&format!("{summary}, fast-forwarded {n} commit(s)")),
+        None => notify(&summary),
+    }
+}
 
-fn handle_selected_branch(repo: &Repository, branch_record: Option<&BranchRecord>) {
+fn handle_selected_branch(
+    repo: &Repository,
+    branch_record: Option<&BranchRecord>,
+    options: &Options,
+) -> Result<(), Error> {
     match branch_record {
+        // Every switch goes through here, so this is where read-only mode stops them
+        Some(branch_record) if options.read_only => println!("{}", branch_record.name),
         Some(branch_record) => {
-            if branch_record.is_current_branch {
+            // Detaching at the current branch's tip still changes something
+            if branch_record.is_current_branch && !options.detach {
                 println!("Already on '{}'", branch_record.name);
-                return;
+                let forwarded = update_selected_branch(repo, branch_record, options)?;
+                if options.notify {
+                    notify_switch(format!("Already on {}", branch_record.name), forwarded);
+                }
+                return Ok(());
+            }
+
+            if options.confirm && !confirm_switch(repo, branch_record) {
+                println!("Not switching");
+                return Ok(());
             }
 
-            println!("Switching to branch '{}'", branch_record.name);
-            if let Err(e) = checkout_branch(repo, branch_record) {
-                println!("Failed to checkout branch: {e}");
-                println!("Please commit your changes or stash them before you switch branches.");
-                exit(1);
+            // Capture where we came from before switching, so a peek can return to it
+            let origin = if options.peek {
+                current_branch(repo)
+            } else {
+                None
             };
+
+            switch_to_branch(repo, branch_record, options, options.detach)?;
+            if let Some(index) = branch_record.stash_index {
+                offer_stash(repo, index);
+            }
+            let forwarded = update_selected_branch(repo, branch_record, options)?;
+            if let Some(command) = &options.exec {
+                run_exec_command(repo, command);
+            }
+            if options.notify {
+                let summary = match head_shorthand(repo) {
+                    _ if options.detach => format!("Detached HEAD at {}", branch_record.name),
+                    Some(current) => format!("Switched to {current}"),
+                    None => format!("Switched to {}", branch_record.name),
+                };
+                notify_switch(summary, forwarded);
+            }
+
+            if let Some(origin) = origin {
+                println!("Press Enter to return to '{}'", origin.name);
+                let mut line = String::new();
+                let _ = io::stdin().read_line(&mut line);
+                switch_to_branch(repo, &origin, options, false)?;
+            }
         }
         _ => println!("Nothing to do"),
     }
+    Ok(())
 }
 
-fn main() {
-    let repo = match Repository::discover(".") {
-        Ok(repo) => repo,
-        Err(e) => panic!("failed to open repo: {}", e),
+// With `detach`, HEAD is left detached at the branch's tip and the branch itself is
+// not checked out
+fn switch_to_branch(
+    repo: &Repository,
+    branch_record: &BranchRecord,
+    options: &Options,
+    detach: bool,
+) -> Result<(), Error> {
+    match describe_commit(repo, &branch_record.commit_sha) {
+        _ if detach => {}
+        Some(description) => println!(
+            "Switching to branch '{}' ({description})",
+            branch_record.name
+        ),
+        None => println!("Switching to branch '{}'", branch_record.name),
+    }
+    let previous = head_position(repo);
+    let old_head = repo.refname_to_id("HEAD").ok();
+    let new_head = Oid::from_str(&branch_record.commit_sha).ok();
+    let tree_id = |oid: Option<Oid>| repo.find_commit(oid?).ok().map(|c| c.tree_id());
+    // Nothing on disk changes in this case, which otherwise looks like a failed switch
+    let same_tree = tree_id(old_head).is_some() && tree_id(old_head) == tree_id(new_head);
+    if let (Some(old), Some(new)) = (old_head, new_head) {
+        if is_commit_signed(repo, old) && !is_commit_signed(repo, new) {
+            println!(
+                "Warning: the tip of '{}' is not signed, unlike the current HEAD",
+                branch_record.name
+            );
+        }
+    }
+    // Not needed when the working tree stays the same, as the changes carry over
+    let stashed = options.autostash && !same_tree && has_local_changes(repo);
+    if stashed {
+        let message = format!("autostash before switching to {}", branch_record.name);
+        stash_changes(repo, &message).map_err(|e| Error::Stash(Box::new(e)))?;
+        println!("Stashed local changes as stash@{{0}}");
+    }
+    let result = match new_head {
+        Some(commit) if detach => checkout_detached(repo, commit),
+        _ => checkout_branch(repo, branch_record),
     };
+    if stashed {
+        restore_autostash(repo, result.is_ok(), options.autostash_pop);
+    }
+    result?;
+    if detach {
+        println!(
+            "HEAD is now detached at {}",
+            branch_record.abbreviated_sha(options.sha_length)
+        );
+        println!("{DETACHED_WARNING}");
+        return Ok(());
+    }
+    // A remote-tracking branch is checked out under a local name
+    let current = head_shorthand(repo).unwrap_or_else(|| branch_record.name.clone());
+    if let Some(previous) = &previous {
+        record_checkout(repo, previous, &current);
+    }
+    if same_tree {
+        println!("Switched to '{current}' (working tree unchanged)");
+    }
+    if let (Some(old), Some(new)) = (old_head, new_head) {
+        sync_submodules(repo, old, new, options.update_submodules);
+    }
+    Ok(())
+}
+
+// After a failed checkout the changes go back where they were. After a successful one
+// they stay stashed unless --autostash-pop asked for them to follow.
+fn restore_autostash(repo: &Repository, switched: bool, pop: bool) {
+    if switched && !pop {
+        println!("Your changes are in stash@{{0}}, restore them with: git stash pop");
+        return;
+    }
+    match pop_stash(repo) {
+        Ok(true) if !switched => println!("Restored the stashed changes"),
+        Ok(true) => println!("Applied the stashed changes and dropped stash@{{0}}"),
+        Ok(false) => {
+            println!("The stashed changes conflict, resolve them in the working tree");
+            println!("They are also kept in stash@{{0}}, drop them with: git stash drop");
+        }
+        Err(e) => {
+            println!("Failed to apply the stashed changes: {e}");
+            println!("They are kept in stash@{{0}}, restore them with: git stash pop");
+        }
+    }
+}
+
+// Runs the --exec command through the shell with the new branch in $GCR_BRANCH. The
+// checkout stays in place even if the command fails.
+fn run_exec_command(repo: &Repository, command: &str) {
+    let branch = head_shorthand(repo).unwrap_or_default();
+    match Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("GCR_BRANCH", &branch)
+        .status()
+    {
+        Ok(status) if status.success() => {}
+        Ok(status) => println!("Command '{command}' failed: {status}"),
+        Err(e) => println!("Failed to run '{command}': {e}"),
+    }
+}
+
+fn checkout_by_name(repo: &Repository, name: &str, options: &Options) -> Result<(), Error> {
+    let mut records = extract_local_branches(repo);
+    if options.stashes {
+        populate_stashes(repo, &mut records);
+    }
+    match records.iter().find(|r| r.name == name) {
+        Some(record) => handle_selected_branch(repo, Some(record), options),
+        None if !remotes_with_branch(repo, name).is_empty() => {
+            checkout_remote_branch(repo, name, options)
+        }
+        None => checkout_revision(repo, name, options),
+    }
+}
+
+const DETACHED_WARNING: &str = "You are not on any branch now: commits made here are \
+only reachable from HEAD and are easily lost once you switch away.";
+
+// Revisions that don't name a branch detach HEAD, which surprises anyone expecting to
+// end up on a branch. Asks first when there is someone at the terminal to answer.
+fn confirm_detach(spec: &str) -> bool {
+    if !termion::is_tty(&io::stdin()) {
+        return true;
+    }
+    println!("'{spec}' is not a branch, so checking it out detaches HEAD.");
+    println!("{DETACHED_WARNING}");
+    print!("Check it out anyway? [y/N] ");
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    let _ = io::stdin().read_line(&mut line);
+    line.trim().eq_ignore_ascii_case("y")
+}
+
+// Anything `git rev-parse` understands, e.g. "main@{yesterday}" or "@{-1}". Revisions
+// naming a branch switch to it, others leave HEAD detached at the commit.
+fn checkout_revision(repo: &Repository, spec: &str, options: &Options) -> Result<(), Error> {
+    let (commit, branch) =
+        resolve_revision(repo, spec).map_err(|source| Error::NoSuchRevision {
+            spec: spec.to_string(),
+            source,
+        })?;
+    if let Some(record) = branch {
+        return handle_selected_branch(repo, Some(&record), options);
+    }
+    if options.read_only {
+        println!("{commit}");
+        return Ok(());
+    }
+    let short = repo
+        .find_object(commit, None)
+        .ok()
+        .and_then(|object| object.short_id().ok())
+        .and_then(|id| id.as_str().map(String::from))
+        .unwrap_or_else(|| commit.to_string());
+    if !confirm_detach(spec) {
+        println!("Not switching");
+        return Ok(());
+    }
+    checkout_detached(repo, commit)?;
+    println!("HEAD is now at {short} ('{spec}', detached)");
+    println!("{DETACHED_WARNING}");
+    Ok(())
+}
+
+// Falls back to a remote branch of the same name, asking which remote to track when
+// several have it and checkout.defaultRemote does not settle it
+fn checkout_remote_branch(repo: &Repository, name: &str, options: &Options) -> Result<(), Error> {
+    let remotes = remotes_with_branch(repo, name);
+    let remote = match remotes.len() {
+        0 => return Err(Error::NoSuchBranch(name.to_string())),
+        1 => remotes[0].clone(),
+        _ => match default_remote(repo).filter(|r| remotes.contains(r)) {
+            Some(remote) => remote,
+            None => prompt_for_remote(name, &remotes)?,
+        },
+    };
+    if options.read_only {
+        println!("{remote}/{name}");
+        return Ok(());
+    }
+    match create_tracking_branch(repo, &remote, name) {
+        Ok(record) => handle_selected_branch(repo, record.as_ref(), options),
+        Err(e) => Err(Error::TrackRemote {
+            name: name.to_string(),
+            remote,
+            source: Box::new(e),
+        }),
+    }
+}
+
+// Shows how much of the working tree the switch rewrites and asks whether to go ahead
+fn confirm_switch(repo: &Repository, branch_record: &BranchRecord) -> bool {
+    match switch_impact(repo, branch_record) {
+        Ok(impact) => println!(
+            "{} file(s) will change: {} added, {} removed, {} modified",
+            impact.total(),
+            impact.added,
+            impact.removed,
+            impact.modified
+        ),
+        Err(e) => println!("Failed to compare with '{}': {e}", branch_record.name),
+    }
+    print!("Switch to '{}'? [Y/n] ", branch_record.name);
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    let _ = io::stdin().read_line(&mut line);
+    !line.trim().eq_ignore_ascii_case("n")
+}
+
+// Work parked with `git stash` on the branch can be picked up right after switching
+fn offer_stash(repo: &Repository, index: usize) {
+    print!("Apply stash@{{{index}}} made on this branch? [y/N] ");
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    let _ = io::stdin().read_line(&mut line);
+    if !line.trim().eq_ignore_ascii_case("y") {
+        return;
+    }
+    match apply_stash(repo, index) {
+        Ok(()) => println!("Applied stash@{{{index}}}, it is kept in the stash list"),
+        Err(e) => println!("Failed to apply stash@{{{index}}}: {e}"),
+    }
+}
+
+fn prompt_for_remote(name: &str, remotes: &[String]) -> Result<String, Error> {
+    println!("Branch '{name}' exists on several remotes:");
+    for (i, remote) in remotes.iter().enumerate() {
+        println!("  {}) {}", i + 1, remote);
+    }
+    print!("Track which remote? ");
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    let _ = io::stdin().read_line(&mut line);
+    let choice = line.trim();
+    let by_number = choice
+        .parse::<usize>()
+        .ok()
+        .and_then(|i| remotes.get(i.wrapping_sub(1)));
+    match by_number.or_else(|| remotes.iter().find(|r| r.as_str() == choice)) {
+        Some(remote) => Ok(remote.clone()),
+        None => Err(Error::NoRemoteSelected),
+    }
+}
+
+// Switches back to where the last checkout made by this tool came from, as long as
+// nothing else has moved HEAD since
+fn undo_last_checkout(repo: &Repository, options: &Options) -> Result<(), Error> {
+    let (from, to) = last_checkout(repo).ok_or(Error::NothingToUndo)?;
+    // Older versions recorded a detached HEAD without its commit
+    if from == "HEAD" {
+        return Err(Error::UndoDetached);
+    }
+    if head_shorthand(repo).as_deref() != Some(to.as_str()) {
+        return Err(Error::HeadMoved { from, to });
+    }
+    checkout_by_name(repo, &from, options)
+}
+
+// Honours GIT_DIR and GIT_WORK_TREE like git, which libgit2 alone does not fully do,
+// and otherwise searches upwards from the current directory
+fn discover_repo() -> Result<Repository, git2::Error> {
+    let repo = match env::var_os("GIT_DIR") {
+        Some(git_dir) => Repository::open(git_dir)?,
+        None => return Repository::discover("."),
+    };
+    if let Some(work_tree) = env::var_os("GIT_WORK_TREE") {
+        repo.set_workdir(Path::new(&work_tree), false)?;
+    }
+    Ok(repo)
+}
+
+// checkout_tree leaves submodules at their old commits, so either update them or
+// point out which ones are now out of date
+fn sync_submodules(repo: &Repository, old: Oid, new: Oid, update: bool) {
+    for path in changed_submodules(repo, old, new) {
+        if !update {
+            println!("Submodule '{path}' changed, run 'git submodule update' to sync it");
+            continue;
+        }
+        match update_submodule(repo, &path) {
+            Ok(()) => println!("Updated submodule '{path}'"),
+            Err(e) => println!("Failed to update submodule '{path}': {e}"),
+        }
+    }
+}
 
-    if repo.state() != RepositoryState::Clean {
-        println!("Repository is not in a clean state (in the middle of a merge?), aborting");
-        exit(1);
+fn open_repo(force_unclean: bool) -> Result<Repository, Error> {
+    let repo = discover_repo().map_err(|e| match e.code() {
+        ErrorCode::NotFound => Error::NotARepo,
+        _ => Error::RepoOpen(e),
+    })?;
+    let state = repo.state();
+    if state != RepositoryState::Clean {
+        if !force_unclean {
+            return Err(Error::DirtyTree);
+        }
+        println!("WARNING: the repository is in the middle of an operation ({state:?}).");
+        println!("WARNING: continuing anyway because of --force-unclean; switching branches");
+        println!("WARNING: now can leave that operation in a broken state.");
+    }
+    Ok(repo)
+}
+
+fn load_records(
+    repo: &Repository,
+    options: &Options,
+    config: &Config,
+    contains: Option<Oid>,
+    names: Option<&[String]>,
+    include_remotes: bool,
+) -> Vec<BranchRecord> {
+    let mut records = match names {
+        Some(names) => extract_named_branches(repo, names),
+        None => extract_branches_with_prefix(repo, options.ref_prefix.as_deref()),
     };
+    if !config.ref_namespaces.is_empty() {
+        let extra = extract_namespace_refs(repo, &config.ref_namespaces);
+        // A local branch that already exists under the same name wins
+        for record in extra {
+            if !records.iter().any(|r| r.name == record.name) {
+                records.push(record);
+            }
+        }
+    }
+    if include_remotes {
+        records.extend(extract_remote_branches(repo));
+        merge_remote_duplicates(repo, &mut records);
+    }
+    pipeline::apply(repo, records, options, config, contains)
+}
 
+// Prints e.g. "main ← feature/x (2h)" for use in shell prompts. Prints nothing at all
+// when anything goes wrong so it never garbles the prompt.
+fn print_prompt_segment() {
+    let repo = match discover_repo() {
+        Ok(repo) => repo,
+        Err(_) => return,
+    };
+    let current = match head_shorthand(&repo) {
+        Some(current) => current,
+        None => return,
+    };
     let mut records = extract_local_branches(&repo);
-    records.sort_by(|a, b| b.time_seconds.cmp(&a.time_seconds));
-    records.truncate(50);
+    sort_records(&mut records, SortKey::Recency);
+    match records.iter().find(|r| !r.is_current_branch) {
+        Some(other) => println!(
+            "{} ← {} ({})",
+            current,
+            other.name,
+            format_compact_age(other.time_seconds, Utc::now())
+        ),
+        None => println!("{current}"),
+    }
+}
 
-    let mut branch_table = BranchTable::new(&records);
+// What ends each record in list output. Branch names cannot contain NUL, so --null
+// output splits safely whatever the names look like.
+fn record_end(options: &Options) -> &'static str {
+    if options.null {
+        "\0"
+    } else {
+        "\n"
+    }
+}
 
-    match render_branch_selection(&mut branch_table) {
-        Ok(res) => handle_selected_branch(&repo, res),
-        Err(e) => {
-            println!("error rendering branch selection: {e}");
-            exit(1);
+// Filters, orderings other than the default, --dedup-tips and limits all need every
+// branch before the first one can be printed
+fn needs_whole_list(options: &Options, config: &Config) -> bool {
+    options.limit().is_some()
+        || options.glob.is_some()
+        || options.contains.is_some()
+        || options.committer.is_some()
+        || options.children_of.is_some()
+        || options.hide_merged
+        || options.unpushed
+        || options.dedup_tips
+        || options.sort_key != SortKey::Recency
+        || options.oldest_first
+        || options.current_first
+        || options.remotes
+        || options.stdin
+        || !config.ref_namespaces.is_empty()
+}
+
+// Writes one JSON object per branch. When nothing needs the whole list, each branch is
+// written as soon as it is read, in the order the refs are read, so memory stays flat.
+// Otherwise the listing pipeline runs first and only the details are read per branch.
+fn print_json_lines(
+    repo: &Repository,
+    options: &Options,
+    config: &Config,
+    listed: impl FnOnce() -> Vec<BranchRecord>,
+) -> Result<(), Error> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut write = |mut record: BranchRecord| -> Result<bool, Error> {
+        load_details(repo, &mut record);
+        let line = serde_json::to_string(&record)?;
+        Ok(write!(out, "{line}{}", record_end(options)).is_ok())
+    };
+    if needs_whole_list(options, config) {
+        for record in listed() {
+            if !write(record)? {
+                break;
+            }
         }
+        return Ok(());
+    }
+    let mut result = Ok(());
+    for_each_local_branch(repo, options.ref_prefix.as_deref(), |mut record| {
+        let one = std::slice::from_mut(&mut record);
+        populate_parents(repo, one, &config.parent);
+        populate_worktrees(repo, one);
+        if options.stashes {
+            populate_stashes(repo, one);
+        }
+        match write(record) {
+            Ok(more) => more,
+            Err(e) => {
+                result = Err(e);
+                false
+            }
+        }
+    });
+    result
+}
+
+const DEFAULT_BRANCH_URL: &str = "{repo}/tree/{branch}";
+
+// Branch names link to the configured URL, or to the branch on the origin's web page
+// with --hyperlinks. Without a usable origin, templates that need it are dropped.
+fn branch_url(repo: &Repository, options: &Options, config: &Config) -> Option<String> {
+    let template = match &config.branch_url {
+        Some(template) => template.as_str(),
+        None if options.hyperlinks => DEFAULT_BRANCH_URL,
+        None => return None,
+    };
+    if !template.contains("{repo}") {
+        return Some(template.to_string());
+    }
+    origin_web_url(repo).map(|web| template.replace("{repo}", &web))
+}
+
+// Prints the branches in picker order along with every signal the orderings use
+fn print_explanation(repo: &Repository, mut records: Vec<BranchRecord>, config: &Config) {
+    if records.iter().any(|r| r.frecency.is_none()) {
+        populate_frecency(repo, &mut records, &config.frecency);
+    }
+    let now = Utc::now();
+    let age = |seconds: Option<i64>| match seconds {
+        Some(seconds) => format_compact_age(seconds, now),
+        None => String::from("-"),
     };
+    let width = records.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    println!(
+        "{:>4}  {:<width$}  {:>9}  {:>13}  {:>8}  {:>8}",
+        "rank", "branch", "committed", "last checkout", "frecency", "unmerged"
+    );
+    for (i, r) in records.iter().enumerate() {
+        println!(
+            "{:>4}  {:<width$}  {:>9}  {:>13}  {:>8.3}  {:>8}",
+            i + 1,
+            r.name,
+            age(Some(r.time_seconds)),
+            age(r.last_checkout_seconds),
+            r.frecency.unwrap_or(0.0),
+            r.unmerged_commits
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| String::from("-")),
+        );
+    }
+}
+
+// Prints the branches the picker would list, in its order, as one JSON array
+fn print_json(repo: &Repository, mut records: Vec<BranchRecord>) -> Result<(), Error> {
+    for record in records.iter_mut() {
+        load_details(repo, record);
+    }
+    println!("{}", serde_json::to_string_pretty(&records)?);
+    Ok(())
+}
+
+// Writes the listed branches, in the order the picker would show them, to a file
+fn export_records(
+    repo: &Repository,
+    mut records: Vec<BranchRecord>,
+    options: &Options,
+    path: &str,
+) -> Result<(), Error> {
+    for record in records.iter_mut() {
+        load_details(repo, record);
+    }
+    let contents = match options.export_format {
+        ExportFormat::Json => serde_json::to_string_pretty(&records)? + "\n",
+        // With --null every field ends in NUL, so there are five per branch
+        ExportFormat::Text => records
+            .iter()
+            .map(|r| {
+                let fields = [
+                    r.name.clone(),
+                    r.short_sha.clone(),
+                    r.format_date(options.date_format, Utc::now()),
+                    r.author_name.clone(),
+                    r.summary.clone(),
+                ];
+                let separator = if options.null { "\0" } else { "\t" };
+                fields.join(separator) + record_end(options)
+            })
+            .collect(),
+    };
+    fs::write(path, contents).map_err(|source| Error::Export {
+        path: path.to_string(),
+        source,
+    })?;
+    println!("Exported {} branches to '{path}'", records.len());
+    Ok(())
+}
+
+// Prints the local branches whose tips are already part of HEAD, one per line, so
+// they can be piped into `git branch -d`
+fn print_merged_here(repo: &Repository, options: &Options) -> Result<(), Error> {
+    let head = repo.refname_to_id("HEAD").map_err(Error::NoHead)?;
+    let mut records = extract_local_branches(repo);
+    sort_records(&mut records, SortKey::Recency);
+    for record in records.iter().filter(|r| !r.is_current_branch) {
+        if is_merged_into(repo, record, head) {
+            print!("{}{}", record.name, record_end(options));
+        }
+    }
+    Ok(())
+}
+
+// Errors that end the program are printed here, once, with an exit code telling them
+// apart: see Error::exit_code
+fn main() {
+    let result = run();
+    profile::report();
+    if let Err(e) = result {
+        eprintln!("{e}");
+        if let Error::CheckoutConflict(_) = e {
+            eprintln!("Please commit your changes or stash them before you switch branches.");
+        }
+        exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let mut options = Options::from_args(env::args().skip(1)).map_err(Error::Usage)?;
+    if options.profile {
+        profile::enable();
+    }
+
+    if options.prompt {
+        print_prompt_segment();
+        return Ok(());
+    }
+
+    let repo = profile::time("discover", || open_repo(options.force_unclean))?;
+
+    let config = profile::time("config", || Config::load(repo.workdir()));
+    options.current_first |= config.current_first;
+    if options.exec.is_none() {
+        options.exec = config.exec.clone();
+    }
+
+    if options.previous {
+        let name = previous_branch_name(&repo).ok_or(Error::NoPreviousBranch)?;
+        return checkout_by_name(&repo, &name, &options);
+    }
+
+    // Unlike --previous, goes by commit time rather than checkout history
+    if options.last {
+        let mut records = extract_local_branches(&repo);
+        sort_records(&mut records, SortKey::Recency);
+        return handle_selected_branch(
+            &repo,
+            records.iter().find(|r| !r.is_current_branch),
+            &options,
+        );
+    }
+
+    if options.merged_here {
+        return print_merged_here(&repo, &options);
+    }
+
+    if options.undo {
+        return undo_last_checkout(&repo, &options);
+    }
+
+    if let Some(name) = &options.checkout {
+        return checkout_by_name(&repo, name, &options);
+    }
+
+    if let Some(name) = &options.checkout_new {
+        if options.read_only {
+            return Err(Error::ReadOnly);
+        }
+        let record = create_branch(&repo, name).map_err(|e| Error::CreateBranch {
+            name: name.clone(),
+            source: Box::new(e),
+        })?;
+        return handle_selected_branch(&repo, record.as_ref(), &options);
+    }
+
+    let contains = match &options.contains {
+        Some(spec) => Some(
+            resolve_commit(&repo, spec).map_err(|source| Error::UnknownCommit {
+                spec: spec.clone(),
+                source,
+            })?,
+        ),
+        None => None,
+    };
+    let names: Option<Vec<String>> = if options.stdin {
+        let names = io::stdin()
+            .lock()
+            .lines()
+            .map_while(Result::ok)
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect();
+        Some(names)
+    } else {
+        None
+    };
+    let load = |include_remotes| {
+        load_records(
+            &repo,
+            &options,
+            &config,
+            contains,
+            names.as_deref(),
+            include_remotes,
+        )
+    };
+
+    if options.json_lines {
+        return print_json_lines(&repo, &options, &config, || load(options.remotes));
+    }
+
+    let mut columns = config
+        .columns
+        .clone()
+        .unwrap_or_else(|| DEFAULT_COLUMNS.to_vec());
+    if options.show_remote && !columns.contains(&Column::Remote) {
+        columns.push(Column::Remote);
+    }
+    let highlight_style = config.highlight_style.as_ref().and_then(|descriptor| {
+        let style = parse_style(descriptor);
+        if style.is_none() {
+            println!("Ignoring invalid highlight-style '{descriptor}'");
+        }
+        style
+    });
+    let display_options = DisplayOptions {
+        align: options.align,
+        wrap: options.wrap,
+        preview_command: options.preview_command.clone(),
+        batch_size: options.batch_size,
+        columns,
+        sha_length: options.sha_length.or(config.sha_length),
+        stat: options.stat,
+        highlight_style,
+        group_by_age: options.group_by_age,
+        max_width: options.max_width,
+        author_max_width: options.author_max_width.or(config.author_max_width),
+        author_first_name: options.author_first_name || config.author_first_name,
+        full_refs: options.full_refs,
+        idle_timeout: options
+            .idle_timeout
+            .or(config.idle_timeout)
+            .map(Duration::from_secs),
+        date_format: options.date_format,
+        now: None,
+        last_fetch_seconds: last_fetch_time(&repo),
+        branch_url: branch_url(&repo, &options, &config),
+        stale_days: options.stale_days.or(config.stale_days),
+        keep_open_on_current: options.keep_open_on_current,
+        flags: config.flags.clone().unwrap_or_else(|| FLAG_ORDER.to_vec()),
+        include_remotes: options.remotes,
+        preselect: last_selection(&repo),
+        save_columns: true,
+    };
+    let records = load(options.remotes);
+    if options.explain {
+        print_explanation(&repo, records, &config);
+        return Ok(());
+    }
+    if options.json {
+        return print_json(&repo, records);
+    }
+    if let Some(path) = &options.export {
+        return export_records(&repo, records, &options, path);
+    }
+    if let (true, Some(pattern)) = (records.is_empty(), &options.glob) {
+        println!("No branches match '{pattern}'");
+        return Ok(());
+    }
+    // Otherwise an empty picker opens whenever there are remote branches
+    if records.is_empty() && options.filters() {
+        println!("No branches match the given filters");
+        return Ok(());
+    }
+    // Nothing to pick from, e.g. in a repository without commits yet. Remote branches
+    // could still be listed with R, so those are checked for too.
+    if records.is_empty() && extract_remote_branches(&repo).is_empty() {
+        println!("No branches found");
+        return Ok(());
+    }
+    let mut branch_table = BranchTable::new(records, &display_options);
+
+    let details = |record: &mut BranchRecord| load_details(&repo, record);
+    let set_description = |record: &BranchRecord, text: &str| {
+        if options.read_only {
+            return Err(Error::ReadOnly);
+        }
+        set_branch_description(&repo, record, text)
+    };
+    let delete = |record: &BranchRecord| {
+        if options.read_only {
+            return Err(Error::ReadOnly);
+        }
+        delete_branch(&repo, record, &config.protected_branches)
+    };
+    // Includes the time spent waiting for keys
+    let selection = profile::time("picker", || {
+        render_branch_selection(
+            &mut branch_table,
+            &config,
+            &load,
+            &details,
+            &set_description,
+            &delete,
+        )
+    });
+    let selection = selection?;
+    if let (Some(record), false) = (selection, options.read_only) {
+        record_selection(&repo, &record.name);
+    }
+    profile::time("checkout", || {
+        handle_selected_branch(&repo, selection, &options)
+    })
 }
@@ -0,0 +1,213 @@
+use git2::{Oid, Repository};
+
+use super::cli::Options;
+use super::config::Config;
+use super::git::{
+    branch_contains, collapse_shared_tips, committer_matches, is_merged_into, load_details,
+    matches_glob, populate_ahead_behind, populate_creation_times, populate_frecency,
+    populate_parents, populate_stashes, populate_unmerged_commits, populate_worktrees,
+    sort_records, BranchRecord, SortKey,
+};
+use super::profile;
+
+// Turns the branches read from the repository into the list the picker shows. Filters
+// run first so the more expensive stages only see the branches that are kept.
+pub fn apply(
+    repo: &Repository,
+    mut records: Vec<BranchRecord>,
+    options: &Options,
+    config: &Config,
+    contains: Option<Oid>,
+) -> Vec<BranchRecord> {
+    profile::time("filter", || {
+        filter(repo, &mut records, options, config, contains)
+    });
+    profile::time("sort", || sort(repo, &mut records, options, config));
+    if options.dedup_tips {
+        collapse_shared_tips(&mut records);
+    }
+    if let Some(limit) = options.limit() {
+        records.truncate(limit);
+    }
+    annotate(repo, &mut records, options);
+    records
+}
+
+fn filter(
+    repo: &Repository,
+    records: &mut Vec<BranchRecord>,
+    options: &Options,
+    config: &Config,
+    contains: Option<Oid>,
+) {
+    // Also shown as a column, so read for every branch rather than only for --children-of
+    populate_parents(repo, records, &config.parent);
+    if let Some(parent) = &options.children_of {
+        records.retain(|r| r.parent.as_deref() == Some(parent.as_str()));
+    }
+    if let Some(pattern) = &options.glob {
+        records.retain(|r| matches_glob(&r.name, pattern));
+    }
+    if let Some(commit) = contains {
+        records.retain(|r| branch_contains(repo, r, commit));
+    }
+    if let Some(pattern) = &options.committer {
+        records.retain(|r| committer_matches(r, pattern));
+    }
+    if options.hide_merged {
+        if let Ok(head) = repo.refname_to_id("HEAD") {
+            records.retain(|r| r.is_current_branch || !is_merged_into(repo, r, head));
+        }
+    }
+    if options.unpushed {
+        populate_ahead_behind(repo, records);
+        records.retain(|r| match r.ahead_behind {
+            Some((ahead, _)) => ahead > 0,
+            None => r.upstream_sha.is_none() && options.include_no_upstream,
+        });
+    }
+}
+
+// Only the sort key in use has its data computed
+fn sort(repo: &Repository, records: &mut [BranchRecord], options: &Options, config: &Config) {
+    match options.sort_key {
+        SortKey::Size => populate_unmerged_commits(repo, records),
+        SortKey::Frecency => populate_frecency(repo, records, &config.frecency),
+        SortKey::Created => populate_creation_times(repo, records),
+        SortKey::Recency => {}
+    }
+    sort_records(records, options.sort_key);
+    if options.oldest_first {
+        records.reverse();
+    }
+    if options.current_first {
+        // Stable sort, so the other branches keep their order
+        records.sort_by_key(|r| !r.is_current_branch);
+    }
+}
+
+fn annotate(repo: &Repository, records: &mut [BranchRecord], options: &Options) {
+    populate_worktrees(repo, records);
+    if options.stashes {
+        populate_stashes(repo, records);
+    }
+    // The summary line counts authors, which needs every branch's details up front
+    if options.stat {
+        for record in records.iter_mut() {
+            load_details(repo, record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::extract_local_branches;
+    use crate::testing::TestRepo;
+
+    // master and its older branches in a line, plus "side" forked from the first commit
+    // and committed to last
+    fn test_repo() -> TestRepo {
+        let test = TestRepo::new();
+        let first = test.commit_at("f", "1", 1_000);
+        test.branch("old", first);
+        test.branch("feature/a", test.commit_at("f", "2", 2_000));
+        test.branch("feature/b", test.commit_at("f", "3", 3_000));
+        test.commit_at("f", "4", 4_000);
+        test.branch("side", first);
+        test.repo.set_head("refs/heads/side").unwrap();
+        test.commit_at("g", "side", 5_000);
+        test.repo.set_head("refs/heads/master").unwrap();
+        test
+    }
+
+    fn listed(test: &TestRepo, args: &[&str]) -> Vec<String> {
+        let args = args.iter().map(|arg| arg.to_string());
+        let options = Options::from_args(args).unwrap();
+        let records = extract_local_branches(&test.repo);
+        apply(&test.repo, records, &options, &Config::default(), None)
+            .into_iter()
+            .map(|r| r.name)
+            .collect()
+    }
+
+    #[test]
+    fn sorts_by_recency_by_default() {
+        let test = test_repo();
+        assert_eq!(
+            listed(&test, &[]),
+            ["side", "master", "feature/b", "feature/a", "old"]
+        );
+    }
+
+    #[test]
+    fn glob_keeps_matching_branches() {
+        let test = test_repo();
+        assert_eq!(
+            listed(&test, &["--glob", "feature/*"]),
+            ["feature/b", "feature/a"]
+        );
+    }
+
+    #[test]
+    fn hide_merged_keeps_the_current_and_unmerged_branches() {
+        let test = test_repo();
+        assert_eq!(listed(&test, &["--hide-merged"]), ["side", "master"]);
+    }
+
+    #[test]
+    fn contains_keeps_branches_with_the_commit() {
+        let test = test_repo();
+        let options = Options::from_args(std::iter::empty()).unwrap();
+        let records = extract_local_branches(&test.repo);
+        let feature_a = test.repo.refname_to_id("refs/heads/feature/a").unwrap();
+        let names: Vec<String> = apply(
+            &test.repo,
+            records,
+            &options,
+            &Config::default(),
+            Some(feature_a),
+        )
+        .into_iter()
+        .map(|r| r.name)
+        .collect();
+        assert_eq!(names, ["master", "feature/b", "feature/a"]);
+    }
+
+    #[test]
+    fn oldest_first_reverses_the_order() {
+        let test = test_repo();
+        assert_eq!(
+            listed(&test, &["--oldest-first"]),
+            ["old", "feature/a", "feature/b", "master", "side"]
+        );
+    }
+
+    #[test]
+    fn current_first_moves_only_the_current_branch() {
+        let test = test_repo();
+        assert_eq!(
+            listed(&test, &["--current-first"]),
+            ["master", "side", "feature/b", "feature/a", "old"]
+        );
+    }
+
+    #[test]
+    fn limit_applies_after_filtering_and_sorting() {
+        let test = test_repo();
+        assert_eq!(
+            listed(&test, &["--glob", "feature/*", "--limit", "1"]),
+            ["feature/b"]
+        );
+        assert_eq!(listed(&test, &["--limit", "2"]), ["side", "master"]);
+    }
+
+    #[test]
+    fn dedup_tips_lists_branches_at_the_same_commit_once() {
+        let test = test_repo();
+        let oid = test.repo.refname_to_id("refs/heads/old").unwrap();
+        test.branch("old-copy", oid);
+        assert_eq!(listed(&test, &[]).len(), 6);
+        assert_eq!(listed(&test, &["--dedup-tips"]).len(), 5);
+    }
+}
@@ -0,0 +1,169 @@
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Quotes a value for safe interpolation into a POSIX shell command
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn strip_ansi_escapes(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.peek() == Some(&'[') {
+                chars.next();
+                // Skip parameters up to and including the final byte
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+// Runs the preview command with every "{}" replaced by the quoted branch name and
+// returns its output. The command is killed if it doesn't finish within the timeout,
+// and also as soon as `superseded` says its output is no longer wanted, which
+// returns None.
+pub fn run_preview_command(
+    command: &str,
+    branch: &str,
+    timeout: Duration,
+    superseded: &mut dyn FnMut() -> bool,
+) -> Option<String> {
+    let command = command.replace("{}", &shell_quote(branch));
+    let mut child = match Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => return Some(format!("failed to run preview command: {e}")),
+    };
+
+    // Read on a separate thread so a chatty command can't block on a full pipe
+    let mut stdout = child.stdout.take();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(stdout) = stdout.as_mut() {
+            let _ = stdout.read_to_string(&mut output);
+        }
+        let _ = tx.send(output);
+    });
+
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if superseded() => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            Ok(None) if start.elapsed() < timeout => thread::sleep(Duration::from_millis(10)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Some(String::from("(preview command timed out)"));
+            }
+        }
+    }
+
+    // Processes spawned by the command may still hold the pipe open
+    Some(match rx.recv_timeout(Duration::from_millis(100)) {
+        Ok(output) => strip_ansi_escapes(&output),
+        Err(_) => String::from("(preview command timed out)"),
+    })
+}
+
+// Waits this long after a request before running it, so that moving quickly through
+// the list only runs the command for where the selection stops
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+// Runs preview commands on a background thread so the picker never waits on them.
+// A newer request drops the ones queued before it and stops the command already
+// running for an older one.
+pub struct PreviewWorker {
+    requests: mpsc::Sender<String>,
+    results: mpsc::Receiver<(String, String)>,
+}
+
+impl PreviewWorker {
+    pub fn new(command: String, timeout: Duration) -> PreviewWorker {
+        let (request_tx, request_rx) = mpsc::channel::<String>();
+        let (result_tx, result_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut next = None;
+            loop {
+                let mut branch = match next.take() {
+                    Some(branch) => branch,
+                    None => match request_rx.recv() {
+                        Ok(branch) => branch,
+                        Err(_) => return,
+                    },
+                };
+                thread::sleep(DEBOUNCE);
+                while let Ok(newer) = request_rx.try_recv() {
+                    branch = newer;
+                }
+                // Also true once the picker has gone away
+                let mut superseded = || match request_rx.try_recv() {
+                    Ok(newer) => {
+                        next = Some(newer);
+                        true
+                    }
+                    Err(mpsc::TryRecvError::Empty) => false,
+                    Err(mpsc::TryRecvError::Disconnected) => true,
+                };
+                let output = run_preview_command(&command, &branch, timeout, &mut superseded);
+                if let Some(output) = output {
+                    if result_tx.send((branch, output)).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        PreviewWorker {
+            requests: request_tx,
+            results: result_rx,
+        }
+    }
+
+    pub fn request(&self, branch: &str) {
+        let _ = self.requests.send(branch.to_string());
+    }
+
+    // The most recently finished (branch, output) pair, if any finished since last time
+    pub fn latest(&self) -> Option<(String, String)> {
+        self.results.try_iter().last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn superseded_commands_are_killed() {
+        let start = Instant::now();
+        let output = run_preview_command("sleep 5", "x", Duration::from_secs(10), &mut || true);
+        assert_eq!(output, None);
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        let output = run_preview_command("echo {}", "x", Duration::from_secs(10), &mut || false);
+        assert_eq!(output.as_deref(), Some("x\n"));
+    }
+}
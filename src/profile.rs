@@ -0,0 +1,62 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Time spent in each phase for --profile, in the order the phases first ran
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static PHASES: Mutex<Vec<Phase>> = Mutex::new(Vec::new());
+
+struct Phase {
+    name: &'static str,
+    total: Duration,
+    calls: usize,
+}
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+// Runs `f`, adding its duration to the phase when profiling. Phases that run many
+// times, like parsing each branch, add up.
+pub fn time<T, F: FnOnce() -> T>(name: &'static str, f: F) -> T {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    if let Ok(mut phases) = PHASES.lock() {
+        match phases.iter_mut().find(|p| p.name == name) {
+            Some(phase) => {
+                phase.total += elapsed;
+                phase.calls += 1;
+            }
+            None => phases.push(Phase {
+                name,
+                total: elapsed,
+                calls: 1,
+            }),
+        }
+    }
+    result
+}
+
+// Prints the timings to stderr, so they stay apart from output meant for scripts
+pub fn report() {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let phases = match PHASES.lock() {
+        Ok(phases) => phases,
+        Err(_) => return,
+    };
+    let width = phases.iter().map(|p| p.name.len()).max().unwrap_or(0);
+    for phase in phases.iter() {
+        eprintln!(
+            "{:<width$}  {:>10.3} ms  {:>6} call(s)",
+            phase.name,
+            phase.total.as_secs_f64() * 1000.0,
+            phase.calls,
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use git2::{Oid, Repository, Signature, Time};
+
+use super::git::BranchRecord;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// A scratch repository for tests, removed again when dropped
+pub struct TestRepo {
+    pub repo: Repository,
+    dir: PathBuf,
+}
+
+impl TestRepo {
+    pub fn new() -> TestRepo {
+        let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+        let dir = env::temp_dir().join(format!("git-checkout-recent-test-{}-{id}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+        repo.set_head("refs/heads/master").unwrap();
+        TestRepo { repo, dir }
+    }
+
+    pub fn workdir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn write(&self, path: &str, contents: &str) {
+        fs::write(self.dir.join(path), contents).unwrap();
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.dir.join(path)).unwrap()
+    }
+
+    // Writes the file, commits it on top of HEAD at the given time and returns the commit
+    pub fn commit_at(&self, path: &str, contents: &str, time_seconds: i64) -> Oid {
+        self.write(path, contents);
+        let mut index = self.repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+        let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature =
+            Signature::new("Test", "test@example.com", &Time::new(time_seconds, 0)).unwrap();
+        let parent = self
+            .repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        self.repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                &format!("Change {path}"),
+                &tree,
+                &parents,
+            )
+            .unwrap()
+    }
+
+    pub fn commit(&self, path: &str, contents: &str) -> Oid {
+        self.commit_at(path, contents, 1_600_000_000)
+    }
+
+    pub fn branch(&self, name: &str, target: Oid) {
+        let commit = self.repo.find_commit(target).unwrap();
+        self.repo.branch(name, &commit, false).unwrap();
+    }
+
+    pub fn branch_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .repo
+            .branches(None)
+            .unwrap()
+            .map(|branch| branch.unwrap().0.name().unwrap().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+// A local branch as listed, with its details already loaded, for tests that don't
+// need a repository behind it
+pub fn record(name: &str, commit_sha: &str, time_seconds: i64) -> BranchRecord {
+    BranchRecord {
+        name: name.to_string(),
+        commit_sha: commit_sha.to_string(),
+        short_sha: String::new(),
+        time_seconds,
+        offset_minutes: 0,
+        summary: format!("Work on {name}"),
+        message: format!("Work on {name}"),
+        ref_name: format!("refs/heads/{name}"),
+        upstream_remote: None,
+        upstream_sha: None,
+        ahead_behind: None,
+        author_name: String::from("Test"),
+        author_email: String::from("test@example.com"),
+        committer_name: String::from("Test"),
+        committer_email: String::from("test@example.com"),
+        is_current_branch: false,
+        is_at_head: false,
+        is_default: false,
+        is_previous: false,
+        recent_commit_count: 0,
+        has_details: true,
+        unmerged_commits: None,
+        frecency: None,
+        last_checkout_seconds: None,
+        remote_status: None,
+        created_seconds: None,
+        description: None,
+        aliases: vec![],
+        stash_index: None,
+        parent: None,
+        upstream_gone: false,
+        in_other_worktree: false,
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
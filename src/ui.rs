@@ -1,156 +1,2143 @@
-use std::{error::Error, io};
-use termion::{event::Key, raw::IntoRawMode, screen::AlternateScreen};
+use chrono::offset::Utc;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    collections::HashMap,
+    io::{self, Write},
+    panic,
+    sync::{Mutex, Once},
+    time::{Duration, Instant},
+};
+use termion::{
+    event::{Key, MouseButton, MouseEvent},
+    input::MouseTerminal,
+    raw::{IntoRawMode, RawTerminal},
+    screen::AlternateScreen,
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
 use tui::{
-    backend::TermionBackend,
-    layout::{Constraint, Layout},
+    backend::{Backend, TermionBackend},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Row, Table, TableState},
-    Terminal,
+    widgets::{Block, Borders, Clear, List, ListState, Paragraph, Text},
+    Frame, Terminal,
 };
 
-use super::git::BranchRecord;
-use super::util::event::{Event, Events};
+use super::config::{save_columns, Config};
+use super::error::Error;
+use super::git::{
+    branch_stats, format_compact_age, is_protected, BranchRecord, DateFormat, RECENT_ACTIVITY_DAYS,
+};
+use super::preview::PreviewWorker;
+use super::util::event::{Config as EventConfig, Event, Events};
+use super::util::table::{cell_width, plain, Cell, Link, Table, TableState, UncoveredLinks};
+
+// Columns that can be shown or hidden next to the always-visible branch name
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Column {
+    LastCommit,
+    Author,
+    Remote,
+    AheadBehind,
+    Created,
+    Age,
+    Parent,
+    Flags,
+}
+
+pub const OPTIONAL_COLUMNS: [Column; 8] = [
+    Column::LastCommit,
+    Column::Author,
+    Column::Remote,
+    Column::AheadBehind,
+    Column::Created,
+    Column::Age,
+    Column::Parent,
+    Column::Flags,
+];
+
+pub const DEFAULT_COLUMNS: [Column; 2] = [Column::LastCommit, Column::Author];
+
+impl Column {
+    fn title(self) -> &'static str {
+        match self {
+            Column::LastCommit => "Last Commit",
+            Column::Author => "Author",
+            Column::Remote => "Remote",
+            Column::AheadBehind => "Upstream",
+            Column::Created => "Created",
+            Column::Age => "Age",
+            Column::Parent => "Parent",
+            Column::Flags => "Flags",
+        }
+    }
+
+    // Relative share of the table width
+    fn weight(self) -> u16 {
+        match self {
+            Column::LastCommit => 45,
+            Column::Author => 15,
+            Column::Remote => 12,
+            Column::AheadBehind => 16,
+            Column::Created => 12,
+            Column::Age => 8,
+            Column::Parent => 15,
+            Column::Flags => 0,
+        }
+    }
+
+    // Columns that always take the same number of cells rather than a share
+    fn fixed_width(self) -> Option<u16> {
+        match self {
+            Column::Flags => Some(FLAG_ORDER.len() as u16),
+            _ => None,
+        }
+    }
+}
+
+// Glyphs that the flags column can show, each one enabled in the config
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Flag {
+    Current,
+    Default,
+    Gone,
+    Stash,
+    Worktree,
+    Unpushed,
+}
+
+// Every flag keeps its place, so the glyphs line up between rows
+pub const FLAG_ORDER: [Flag; 6] = [
+    Flag::Current,
+    Flag::Default,
+    Flag::Gone,
+    Flag::Stash,
+    Flag::Worktree,
+    Flag::Unpushed,
+];
+
+impl Flag {
+    fn applies(self, r: &BranchRecord) -> bool {
+        match self {
+            Flag::Current => r.is_current_branch,
+            Flag::Default => r.is_default,
+            Flag::Gone => r.upstream_gone,
+            Flag::Stash => r.stash_index.is_some(),
+            Flag::Worktree => r.in_other_worktree,
+            Flag::Unpushed => matches!(r.ahead_behind, Some((ahead, _)) if ahead > 0),
+        }
+    }
+
+    fn glyph(self) -> Text<'static> {
+        let (glyph, color) = match self {
+            Flag::Current => ("*", Color::Green),
+            Flag::Default => ("◆", Color::Blue),
+            Flag::Gone => ("⊘", Color::Red),
+            Flag::Stash => ("$", Color::Magenta),
+            Flag::Worktree => ("⊞", Color::Cyan),
+            Flag::Unpushed => ("↑", Color::Yellow),
+        };
+        Text::styled(glyph, Style::default().fg(color))
+    }
+}
+
+fn flags_cell(r: &BranchRecord, enabled: &[Flag]) -> Cell {
+    FLAG_ORDER
+        .iter()
+        .map(|flag| {
+            if enabled.contains(flag) && flag.applies(r) {
+                flag.glyph()
+            } else {
+                Text::raw(" ")
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Default)]
+pub struct DisplayOptions {
+    pub align: bool,
+    pub wrap: bool,
+    pub preview_command: Option<String>,
+    // Number of branches to show initially and to add on each "show more"; 0 shows all
+    pub batch_size: usize,
+    pub columns: Vec<Column>,
+    // Fixed SHA abbreviation length; by default the shortest unambiguous one is used
+    pub sha_length: Option<usize>,
+    // Show a summary line of all listed branches
+    pub stat: bool,
+    pub highlight_style: Option<Style>,
+    // Insert "Today", "This week" and "Older" headers between branches
+    pub group_by_age: bool,
+    // Centre the picker in at most this many columns
+    pub max_width: Option<u16>,
+    pub author_max_width: Option<usize>,
+    // Show only the first word of author names
+    pub author_first_name: bool,
+    // Show e.g. "refs/heads/main" instead of "main"
+    pub full_refs: bool,
+    // Quit without a selection after this long without a key press
+    pub idle_timeout: Option<Duration>,
+    pub date_format: DateFormat,
+    // Fixed time that ages are rendered relative to, instead of the clock
+    pub now: Option<DateTime<Utc>>,
+    // Unix time of the last fetch, shown so stale remote data is recognisable
+    pub last_fetch_seconds: Option<i64>,
+    // URL that branch names link to, with "{branch}" standing for the name
+    pub branch_url: Option<String>,
+    // Branches without commits for more than this many days are shown in red
+    pub stale_days: Option<i64>,
+    // Enter on the current branch keeps the picker open instead of returning it
+    pub keep_open_on_current: bool,
+    // Glyphs shown in the flags column
+    pub flags: Vec<Flag>,
+    // Start out listing remote-tracking branches too, as if R was pressed
+    pub include_remotes: bool,
+    // Branch highlighted when the picker opens, if listed, instead of the first one
+    pub preselect: Option<String>,
+    // Store the columns chosen with Tab in the config file
+    pub save_columns: bool,
+}
+
+impl DisplayOptions {
+    fn now(&self) -> DateTime<Utc> {
+        self.now.unwrap_or_else(Utc::now)
+    }
+}
+
+fn age_bucket(time_seconds: i64, now: i64) -> &'static str {
+    match now - time_seconds {
+        age if age < 24 * 60 * 60 => "Today",
+        age if age < 7 * 24 * 60 * 60 => "This week",
+        _ => "Older",
+    }
+}
+
+// Each branch is rendered as a block of rows: name and commit info, summary, spacer
+const ROWS_PER_RECORD: usize = 3;
+
+// Branches moved by Page Up / Page Down until a draw tells how many fit
+const PAGE_SIZE: usize = 10;
 
-pub struct BranchTable<'a> {
+// Smallest area that fits the table borders, header and a single branch
+const MIN_TABLE_WIDTH: u16 = 20;
+const MIN_TABLE_HEIGHT: u16 = 7;
+
+const MARGIN: u16 = 5;
+
+// Terminals at least this wide show branches in two side-by-side columns
+const TWO_COLUMN_MIN_WIDTH: u16 = 160;
+
+pub struct BranchTable {
     state: TableState,
-    items: Vec<Vec<String>>,
+    selected: Option<usize>,
+    options: DisplayOptions,
+    // Number of branches per column when rendering two columns, if any
+    column_height: Option<usize>,
+    // Number of branches that fit on the screen as of the last draw
+    page_size: usize,
+    items: Vec<Vec<Cell>>,
+    // Hyperlink of the name in each row of `items`, if any
+    links: Vec<Option<String>>,
+    // Row in `items` where each visible record starts, as group headers take up rows too
+    record_rows: Vec<usize>,
     header: Vec<String>,
-    records: &'a [BranchRecord],
+    records: Vec<BranchRecord>,
+    // Only the first `visible` records are materialized into `items`
+    visible: usize,
+    // Typed with /, narrowing `records` to the branches that match it
+    filter: String,
+    // Branches that do not match the filter, with their position in the full list
+    filtered_out: Vec<(usize, BranchRecord)>,
+    order: ListOrder,
+    // Position of each branch in the order it was loaded in, to return to it
+    loaded_rank: HashMap<String, usize>,
+    // Screen area of the rows as of the last draw, with the row of `items` shown at
+    // its top, to find the branch under a mouse click
+    row_areas: Vec<(Rect, usize)>,
+    // Branch names drawn as hyperlinks in the last draw and still showing
+    drawn_links: Vec<Link>,
+    // Taken by the first call to init
+    preselect: Option<String>,
+    // Widest text in the name column of `items`, which also holds the remote status,
+    // description and group headers
+    name_width: usize,
+}
+
+// Order of the branches in the picker, cycled with S
+#[derive(Clone, Copy, PartialEq)]
+enum ListOrder {
+    // As loaded: by recency unless another order was asked for on the command line
+    Loaded,
+    Name,
+    Author,
+}
+
+impl ListOrder {
+    fn next(self) -> ListOrder {
+        match self {
+            ListOrder::Loaded => ListOrder::Name,
+            ListOrder::Name => ListOrder::Author,
+            ListOrder::Author => ListOrder::Loaded,
+        }
+    }
 }
 
-impl<'a> BranchTable<'a> {
-    pub fn new(records: &'a [BranchRecord]) -> BranchTable<'a> {
-        let (data, header) = get_table_data_from_branch_records(records);
-        BranchTable {
+impl BranchTable {
+    pub fn new(records: Vec<BranchRecord>, options: &DisplayOptions) -> BranchTable {
+        let mut table = BranchTable {
             state: TableState::default(),
-            items: data,
-            header,
+            selected: None,
+            options: options.clone(),
+            column_height: None,
+            page_size: PAGE_SIZE,
+            items: vec![],
+            links: vec![],
+            record_rows: vec![],
+            header: vec![],
             records,
-        }
+            visible: 0,
+            filter: String::new(),
+            filtered_out: vec![],
+            order: ListOrder::Loaded,
+            loaded_rank: HashMap::new(),
+            row_areas: vec![],
+            drawn_links: vec![],
+            name_width: 0,
+            preselect: options.preselect.clone(),
+        };
+        table.rank_loaded();
+        table.visible = table.batch_size().min(table.records.len());
+        table.rebuild_items();
+        table
     }
 
-    pub fn init(&mut self) {
-        self.state.select(Some(0));
+    fn batch_size(&self) -> usize {
+        match self.options.batch_size {
+            0 => self.records.len(),
+            n => n,
+        }
     }
 
-    pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i + 3 >= self.items.len() {
-                    i
-                } else {
-                    i + 3
+    fn rebuild_items(&mut self) {
+        let records = &self.records[..self.visible];
+        let (data, header) = get_table_data_from_branch_records(records, &self.options);
+        self.items = vec![];
+        self.links = vec![];
+        self.record_rows = vec![];
+        let now = self.options.now().timestamp();
+        let mut group = None;
+        for (r, rows) in records.iter().zip(data.chunks(ROWS_PER_RECORD)) {
+            if self.options.group_by_age {
+                let bucket = age_bucket(r.time_seconds, now);
+                if group != Some(bucket) {
+                    group = Some(bucket);
+                    self.items
+                        .push(vec![plain(""), plain(format!("── {bucket} ──"))]);
+                    self.links.push(None);
                 }
             }
+            self.record_rows.push(self.items.len());
+            self.items.extend(rows.iter().cloned());
+            let link = self
+                .options
+                .branch_url
+                .as_ref()
+                .map(|url| url.replace("{branch}", &r.name));
+            self.links.push(link);
+            self.links.resize(self.items.len(), None);
+        }
+        self.header = header;
+        self.name_width = self
+            .items
+            .iter()
+            .filter_map(|row| row.get(1))
+            .map(|cell| cell_width(cell))
+            .max()
+            .unwrap_or(0);
+        let row = self.selected.and_then(|i| self.record_rows.get(i).copied());
+        self.state.select(row);
+    }
+
+    // Row range in `items` covering the visible records from `start` up to `end`
+    fn rows_between(&self, start: usize, end: usize) -> std::ops::Range<usize> {
+        let row = |i: usize| self.record_rows.get(i).copied().unwrap_or(self.items.len());
+        row(start)..row(end)
+    }
+
+    // Materializes the next batch of branches. Returns false if all are already shown.
+    pub fn show_more(&mut self) -> bool {
+        if self.visible >= self.records.len() {
+            return false;
+        }
+        self.visible = (self.visible + self.batch_size()).min(self.records.len());
+        self.rebuild_items();
+        true
+    }
+
+    // Loads the details of any newly visible branches before they are drawn
+    pub fn load_visible_details(&mut self, load_details: &dyn Fn(&mut BranchRecord)) {
+        let pending: Vec<usize> = (0..self.visible)
+            .filter(|&i| !self.records[i].has_details)
+            .collect();
+        if pending.is_empty() {
+            return;
+        }
+        for i in pending {
+            load_details(&mut self.records[i]);
+        }
+        self.rebuild_items();
+    }
+
+    pub fn hidden_count(&self) -> usize {
+        self.records.len() - self.visible
+    }
+
+    // Grows the visible window, batch by batch, until it includes the given branch
+    fn ensure_visible(&mut self, index: usize) {
+        while index >= self.visible && self.show_more() {}
+    }
+
+    // Replaces the listed branches, keeping the selection on the same branch if it
+    // is still present. Whenever the list changes like this, the view goes back to
+    // the top, scrolled only as far as needed to show the selection.
+    pub fn set_records(
+        &mut self,
+        records: Vec<BranchRecord>,
+        load_details: &dyn Fn(&mut BranchRecord),
+    ) {
+        let selected_name = self.selected_record().map(|r| r.name.clone());
+        self.records = records;
+        self.filtered_out.clear();
+        self.rank_loaded();
+        self.apply_order();
+        self.apply_filter(load_details);
+        self.visible = self.visible.max(self.batch_size()).min(self.records.len());
+        self.rebuild_items();
+        self.state.scroll_to_top();
+        let found = match selected_name {
+            Some(name) => self.select_by_name(&name),
+            None => false,
+        };
+        if !found {
+            self.init();
+        }
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    // Narrows the list to the branches whose name or summary contains the query,
+    // ignoring case, and selects the first one. Clearing the query lists the others
+    // again in their original place.
+    pub fn set_filter(&mut self, query: &str, load_details: &dyn Fn(&mut BranchRecord)) {
+        for (index, record) in std::mem::take(&mut self.filtered_out) {
+            let index = index.min(self.records.len());
+            self.records.insert(index, record);
+        }
+        self.apply_order();
+        self.filter = query.to_string();
+        self.apply_filter(load_details);
+        self.visible = self.batch_size().min(self.records.len());
+        self.rebuild_items();
+        self.state.scroll_to_top();
+        self.init();
+    }
+
+    fn rank_loaded(&mut self) {
+        self.loaded_rank = self
+            .records
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (r.ref_name.clone(), i))
+            .collect();
+    }
+
+    // Sorts by the next order, keeping the same branch selected. Sorting by author
+    // needs every branch's details first.
+    fn cycle_order(&mut self, load_details: &dyn Fn(&mut BranchRecord)) {
+        let selected = self.selected_record().map(|r| r.ref_name.clone());
+        self.order = self.order.next();
+        if self.order == ListOrder::Author {
+            let records = self.records.iter_mut();
+            let hidden = self.filtered_out.iter_mut().map(|(_, r)| r);
+            for record in records.chain(hidden).filter(|r| !r.has_details) {
+                load_details(record);
+            }
+        }
+        self.apply_order();
+        self.rebuild_items();
+        self.state.scroll_to_top();
+        match selected {
+            Some(ref_name) => {
+                self.select_where(|r| r.ref_name == ref_name);
+            }
+            None => self.init(),
+        }
+    }
+
+    // Stable sorts, so ties keep the loaded order
+    fn apply_order(&mut self) {
+        let rank = &self.loaded_rank;
+        let loaded = |r: &BranchRecord| rank.get(&r.ref_name).copied().unwrap_or(usize::MAX);
+        self.records.sort_by_key(|r| loaded(r));
+        match self.order {
+            ListOrder::Loaded => {}
+            ListOrder::Name => self.records.sort_by_key(|r| r.name.to_lowercase()),
+            ListOrder::Author => self.records.sort_by_key(|r| r.author_name.to_lowercase()),
+        }
+    }
+
+    // Summaries are only read with the other details, so those are loaded for the
+    // branches whose name alone doesn't match
+    fn apply_filter(&mut self, load_details: &dyn Fn(&mut BranchRecord)) {
+        if self.filter.is_empty() {
+            return;
+        }
+        let query = self.filter.to_lowercase();
+        let matches = |r: &mut BranchRecord| {
+            if r.name.to_lowercase().contains(&query) {
+                return true;
+            }
+            if !r.has_details {
+                load_details(r);
+            }
+            r.summary.to_lowercase().contains(&query)
+        };
+        for (index, mut record) in std::mem::take(&mut self.records).into_iter().enumerate() {
+            if matches(&mut record) {
+                self.records.push(record);
+            } else {
+                self.filtered_out.push((index, record));
+            }
+        }
+    }
+
+    fn select(&mut self, index: Option<usize>) {
+        if let Some(i) = index {
+            self.ensure_visible(i);
+        }
+        self.selected = index;
+        self.state
+            .select(index.and_then(|i| self.record_rows.get(i).copied()));
+    }
+
+    pub fn init(&mut self) {
+        if let Some(name) = self.preselect.take() {
+            if self.select_by_name(&name) {
+                return;
+            }
+        }
+        if self.records.is_empty() {
+            self.select(None);
+        } else {
+            self.select(Some(0));
+        }
+    }
+
+    // The movement methods return false when the selection could not move
+    fn next(&mut self) -> bool {
+        // Moving past the last visible branch loads the next batch
+        let i = match self.selected {
+            Some(i) if i + 1 < self.records.len() => i + 1,
+            Some(_) if self.options.wrap => 0,
+            Some(i) => i,
+            None => 0,
+        };
+        self.move_to(i, self.records.len())
+    }
+
+    // Wrapping backwards lands on the last branch, loading the batches before it
+    fn previous(&mut self) -> bool {
+        let len = self.records.len();
+        let i = match self.selected {
+            Some(i) if i > 0 => i - 1,
+            Some(_) if self.options.wrap => len.saturating_sub(1),
+            Some(i) => i,
             None => 0,
         };
-        self.state.select(Some(i));
+        self.move_to(i, len)
+    }
+
+    fn move_to(&mut self, index: usize, len: usize) -> bool {
+        if len == 0 || self.selected == Some(index) {
+            return false;
+        }
+        self.select(Some(index));
+        true
+    }
+
+    // Moves to the same position in the neighbouring column, when two columns are shown
+    fn left(&mut self) -> bool {
+        match (self.selected, self.column_height) {
+            (Some(i), Some(height)) if i >= height => self.move_to(i - height, self.visible),
+            _ => false,
+        }
+    }
+
+    fn right(&mut self) -> bool {
+        match (self.selected, self.column_height) {
+            (Some(i), Some(height)) if i + height < self.visible => {
+                self.move_to(i + height, self.visible)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn select_by_prefix(&mut self, prefix: &str) {
+        let prefix = prefix.to_lowercase();
+        if let Some(i) = self
+            .records
+            .iter()
+            .position(|r| r.name.to_lowercase().starts_with(&prefix))
+        {
+            self.select(Some(i));
+        }
     }
 
-    pub fn previous(&mut self) {
-        let i = match self.state.selected() {
+    pub fn select_by_name(&mut self, name: &str) -> bool {
+        match self.records.iter().position(|r| r.name == name) {
             Some(i) => {
-                if i < 3 {
-                    i
-                } else {
-                    i - 3
-                }
+                self.select(Some(i));
+                true
             }
-            None => 0,
+            None => false,
+        }
+    }
+
+    fn first(&mut self) -> bool {
+        self.move_to(0, self.records.len())
+    }
+
+    fn last(&mut self) -> bool {
+        match self.records.len() {
+            0 => false,
+            len => self.move_to(len - 1, len),
+        }
+    }
+
+    // Moves a screenful at a time, stopping at the first and last branch
+    fn page_down(&mut self) -> bool {
+        let len = self.records.len();
+        match self.selected {
+            Some(i) => self.move_to((i + self.page_size).min(len.saturating_sub(1)), len),
+            None => self.first(),
+        }
+    }
+
+    fn page_up(&mut self) -> bool {
+        match self.selected {
+            Some(i) => self.move_to(i.saturating_sub(self.page_size), self.visible),
+            None => self.first(),
+        }
+    }
+
+    pub fn select_default(&mut self) -> bool {
+        self.select_where(|r| r.is_default)
+    }
+
+    fn select_where(&mut self, f: impl Fn(&BranchRecord) -> bool) -> bool {
+        match self.records.iter().position(f) {
+            Some(i) => {
+                self.select(Some(i));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Branch drawn at the given screen position, if any. Group headers belong to none.
+    fn record_at(&self, x: u16, y: u16) -> Option<usize> {
+        let (area, first_row) = self.row_areas.iter().find(|(area, _)| {
+            (area.left()..area.right()).contains(&x) && (area.top()..area.bottom()).contains(&y)
+        })?;
+        let row = first_row + (y - area.top()) as usize;
+        let index = self
+            .record_rows
+            .partition_point(|&r| r <= row)
+            .checked_sub(1)?;
+        (row < self.record_rows[index] + ROWS_PER_RECORD && row < self.items.len()).then_some(index)
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.options.columns
+    }
+
+    pub fn toggle_column(&mut self, column: Column) {
+        if self.options.columns.contains(&column) {
+            self.options.columns.retain(|c| *c != column);
+        } else {
+            // Keep the columns in their canonical order
+            let mut columns = self.options.columns.clone();
+            columns.push(column);
+            self.options.columns = OPTIONAL_COLUMNS
+                .iter()
+                .copied()
+                .filter(|c| columns.contains(c))
+                .collect();
+        }
+        self.rebuild_items();
+    }
+
+    // Between the exact commit time and the format the picker started with
+    fn toggle_absolute_dates(&mut self, initial: DateFormat) {
+        self.options.date_format = match self.options.date_format {
+            DateFormat::Absolute => initial,
+            _ => DateFormat::Absolute,
         };
-        self.state.select(Some(i));
+        self.rebuild_items();
     }
 
     pub fn deselect(&mut self) {
-        self.state.select(None);
+        self.select(None);
+    }
+
+    // Lists the branches hidden behind the highlighted one right below it
+    fn expand_selected(&mut self) -> bool {
+        let i = match self.selected {
+            Some(i) if !self.records[i].aliases.is_empty() => i,
+            _ => return false,
+        };
+        let aliases = std::mem::take(&mut self.records[i].aliases);
+        // Ranked with the branch they were hidden behind, so they stay right after it
+        if let Some(&rank) = self.loaded_rank.get(&self.records[i].ref_name) {
+            for alias in &aliases {
+                self.loaded_rank.insert(alias.ref_name.clone(), rank);
+            }
+        }
+        self.visible += aliases.len();
+        self.records.splice(i + 1..i + 1, aliases);
+        self.rebuild_items();
+        self.select(Some(i));
+        true
+    }
+
+    pub fn set_selected_description(&mut self, description: &str) {
+        if let Some(record) = self.selected.and_then(|i| self.records.get_mut(i)) {
+            record.description = Some(description.to_string()).filter(|d| !d.is_empty());
+        }
+        self.rebuild_items();
+    }
+
+    // Takes the highlighted branch out of the list, e.g. once it was deleted, and
+    // highlights the one that moved up into its place. Branches hidden behind it by
+    // --dedup-tips are listed in its stead.
+    fn remove_selected(&mut self) {
+        let i = match self.selected {
+            Some(i) if i < self.records.len() => i,
+            _ => return,
+        };
+        let mut removed = self.records.remove(i);
+        self.loaded_rank.remove(&removed.ref_name);
+        let aliases = std::mem::take(&mut removed.aliases);
+        self.visible = (self.visible + aliases.len()).saturating_sub(1);
+        self.records.splice(i..i, aliases);
+        self.rebuild_items();
+        self.state.scroll_to_top();
+        match self.records.len() {
+            0 => self.select(None),
+            len => self.select(Some(i.min(len - 1))),
+        }
     }
 
-    pub fn selected_record(&mut self) -> Option<&BranchRecord> {
-        match self.state.selected() {
-            Some(row) => self.records.get(row / 3),
+    pub fn selected_record(&self) -> Option<&BranchRecord> {
+        match self.selected {
+            Some(i) => self.records.get(i),
             _ => None,
         }
     }
 }
 
-fn get_table_data_from_branch_records(records: &[BranchRecord]) -> (Vec<Vec<String>>, Vec<String>) {
+fn pad_to_width(s: &str, width: usize) -> String {
+    let padding = width.saturating_sub(s.width());
+    format!("{}{}", s, " ".repeat(padding))
+}
+
+// Cuts the string to at most `width` terminal columns, ending in "…" when shortened
+fn truncate_to_width(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return s.to_string();
+    }
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        truncated.push(c);
+        used += w;
+    }
+    truncated + "…"
+}
+
+fn format_author(name: &str, options: &DisplayOptions) -> String {
+    let name = if options.author_first_name {
+        name.split_whitespace().next().unwrap_or(name)
+    } else {
+        name
+    };
+    match options.author_max_width {
+        Some(width) => truncate_to_width(name, width),
+        None => name.to_string(),
+    }
+}
+
+// The header and rows rendered for the branches, ROWS_PER_RECORD rows per branch
+pub fn get_table_data_from_branch_records(
+    records: &[BranchRecord],
+    options: &DisplayOptions,
+) -> (Vec<Vec<Cell>>, Vec<String>) {
     let mut data = vec![];
-    let header = vec![String::from("Name"), String::from("Last Commit")];
-    for r in records {
-        let mut name = r.name.clone();
-        if r.is_current_branch {
-            name = String::from("* ") + &name;
-        }
-        let commit_info = format!(
-            "{} ({}) {}",
-            &r.commit_sha[..8],
-            r.pretty_format_date(),
-            r.author_name
-        );
-        let row = vec![name, commit_info.clone()];
-        data.push(row);
-        let row = vec![String::from(""), r.summary.clone()];
-        data.push(row);
-        let row = vec![String::from(""), String::from("")];
-        data.push(row);
+    // The first column holds the current / default / at-HEAD marker, so names line up
+    let mut header = vec![String::from(""), String::from("Name")];
+    header.extend(options.columns.iter().map(|c| String::from(c.title())));
+    let now = options.now();
+    let dates: Vec<String> = records
+        .iter()
+        .map(|r| match options.date_format {
+            // Already has the ISO date in parentheses
+            DateFormat::Both => r.format_date(DateFormat::Both, now),
+            format => format!("({})", r.format_date(format, now)),
+        })
+        .collect();
+    let date_width = if options.align {
+        dates.iter().map(|d| d.width()).max().unwrap_or(0)
+    } else {
+        0
+    };
+    let age_days = |r: &BranchRecord| (now.timestamp() - r.time_seconds).max(0) / (24 * 60 * 60);
+    // Right-aligned, so the widest number sets the width
+    let age_width = records
+        .iter()
+        .map(|r| format!("{}d", age_days(r)).len())
+        .max()
+        .unwrap_or(0);
+    let upstream_column = options.columns.contains(&Column::AheadBehind);
+    for (r, date) in records.iter().zip(dates.iter()) {
+        // E.g. "↑2 ↓1", unless the upstream column already says so
+        let upstream_counts = match r.ahead_behind {
+            Some((ahead, behind)) if !upstream_column && (ahead, behind) != (0, 0) => {
+                let counts = [("↑", ahead), ("↓", behind)];
+                let marks = counts.iter().filter(|(_, n)| *n > 0);
+                Some(
+                    marks
+                        .map(|(arrow, n)| format!("{arrow}{n}"))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                )
+            }
+            _ => None,
+        };
+        let mut name = if options.full_refs {
+            r.ref_name.clone()
+        } else {
+            r.name.clone()
+        };
+        if !r.aliases.is_empty() {
+            name = format!("{name} (+{})", r.aliases.len());
+        }
+        if r.stash_index.is_some() {
+            name = format!("{name} [wip]");
+        }
+        if r.is_remote() && !options.full_refs {
+            name = format!("{name} (remote)");
+        }
+        let marker = if r.is_current_branch {
+            "*"
+        } else if r.is_default {
+            "^"
+        } else if r.is_at_head {
+            "="
+        } else {
+            ""
+        };
+        let summary = if r.recent_commit_count > 0 {
+            format!(
+                "[{} in {}d] {}",
+                r.recent_commit_count, RECENT_ACTIVITY_DAYS, r.summary
+            )
+        } else {
+            r.summary.clone()
+        };
+
+        let blank = || vec![plain(""), plain("")];
+        let mut rows = [vec![plain(marker), plain(name)], blank(), blank()];
+        if let Some(status) = &r.remote_status {
+            rows[1][1] = plain(status.clone());
+        }
+        // Only the first line of longer descriptions fits
+        if let Some(line) = r.description.as_deref().and_then(|d| d.lines().next()) {
+            rows[2][1] = plain(line);
+        }
+        for column in &options.columns {
+            let cells = match column {
+                // The SHA is dimmed and the author tinted so the dense info stays scannable
+                Column::LastCommit => {
+                    let mut first = vec![
+                        Text::styled(
+                            r.abbreviated_sha(options.sha_length).to_string(),
+                            sha_style(),
+                        ),
+                        Text::raw(format!(" {}", pad_to_width(date, date_width))),
+                    ];
+                    if let Some(counts) = &upstream_counts {
+                        first.push(Text::raw(format!(" {counts}")));
+                    }
+                    [first, plain(summary.clone())]
+                }
+                Column::Author => [
+                    vec![Text::styled(
+                        format_author(&r.author_name, options),
+                        author_style(),
+                    )],
+                    plain(""),
+                ],
+                Column::Remote => [
+                    plain(r.upstream_remote.clone().unwrap_or_default()),
+                    plain(""),
+                ],
+                Column::AheadBehind => match r.ahead_behind {
+                    Some((0, 0)) => [plain(divergence_bar(0, 0)), plain("in sync")],
+                    Some((ahead, behind)) => [
+                        plain(divergence_bar(ahead, behind)),
+                        plain(format!("-{behind} +{ahead}")),
+                    ],
+                    None => [plain(""), plain("")],
+                },
+                Column::Created => [
+                    plain(
+                        r.created_seconds
+                            .map(|created| format!("{} ago", format_compact_age(created, now)))
+                            .unwrap_or_default(),
+                    ),
+                    plain(""),
+                ],
+                Column::Age => [
+                    plain(format!("{:>age_width$}", format!("{}d", age_days(r)))),
+                    plain(""),
+                ],
+                Column::Parent => [plain(r.parent.clone().unwrap_or_default()), plain("")],
+                Column::Flags => [flags_cell(r, &options.flags), plain("")],
+            };
+            let [first, second] = cells;
+            rows[0].push(first);
+            rows[1].push(second);
+            rows[2].push(plain(""));
+        }
+        if options.stale_days.is_some_and(|days| age_days(r) > days) {
+            for cell in rows.iter_mut().flatten() {
+                *cell = with_row_style(std::mem::take(cell), stale_style());
+            }
+        }
+        data.extend(rows);
     }
     (data, header)
 }
 
-pub fn render_branch_selection<'a>(
-    table: &'a mut BranchTable,
-) -> Result<Option<&'a BranchRecord>, Box<dyn Error>> {
-    // Terminal initialization
+// Longest half of the ahead/behind bar, reached from 8 commits on
+const DIVERGENCE_BAR_MAX: usize = 4;
+
+// Bounds of the name column, which is sized to the longest name
+const NAME_MIN_WIDTH: u16 = 4;
+const NAME_MAX_PERCENT: u32 = 40;
+
+// E.g. " ◀◀│▶   " for 2-3 commits behind and 1 ahead. Each half grows by one mark as
+// the count doubles so large divergences still fit.
+fn divergence_bar(ahead: usize, behind: usize) -> String {
+    let marks = |count: usize| (usize::BITS - count.leading_zeros()) as usize;
+    let width = DIVERGENCE_BAR_MAX;
+    format!(
+        "{:>width$}│{:<width$}",
+        "◀".repeat(marks(behind).min(width)),
+        "▶".repeat(marks(ahead).min(width)),
+    )
+}
+
+// The name column is as wide as the longest name, up to NAME_MAX_PERCENT of the table,
+// and the other columns share what is left
+fn column_widths(columns: &[Column], table_width: u16, name_width: usize) -> Vec<Constraint> {
+    let name_max = (u32::from(table_width) * NAME_MAX_PERCENT / 100) as u16;
+    let name_width = (name_width.min(u16::MAX as usize) as u16)
+        .clamp(NAME_MIN_WIDTH, name_max.max(NAME_MIN_WIDTH));
+    let total: u16 = columns.iter().map(|c| c.weight()).sum::<u16>().max(1);
+    // Percentages are of the whole table, so leave room for the borders, the column
+    // gaps and the marker column, which also holds the ">> " highlight symbol, as well
+    // as for the name and fixed-width columns
+    let fixed: u32 = columns
+        .iter()
+        .filter_map(|c| c.fixed_width())
+        .chain([name_width])
+        .map(|width| u32::from(width) + 1)
+        .sum();
+    let fixed_percent = (fixed * 100).div_ceil(u32::from(table_width.max(1)));
+    let share = 90u16.saturating_sub(fixed_percent.min(90) as u16);
+    let mut widths = vec![Constraint::Length(4), Constraint::Length(name_width)];
+    widths.extend(columns.iter().map(|c| match c.fixed_width() {
+        Some(width) => Constraint::Length(width),
+        None => Constraint::Percentage(c.weight() * share / total),
+    }));
+    widths
+}
+
+fn stale_style() -> Style {
+    Style::default().fg(Color::Red)
+}
+
+// Gives the spans that would take the row style this one instead
+fn with_row_style(cell: Cell, style: Style) -> Cell {
+    cell.into_iter()
+        .map(|text| match text {
+            Text::Raw(s) => Text::Styled(s, style),
+            styled => styled,
+        })
+        .collect()
+}
+
+fn sha_style() -> Style {
+    Style::default().modifier(Modifier::DIM)
+}
+
+fn author_style() -> Style {
+    Style::default().fg(Color::Cyan)
+}
+
+fn default_highlight_style() -> Style {
+    Style::default().fg(Color::Yellow).modifier(Modifier::BOLD)
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    let color = match name {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" => Color::Gray,
+        "darkgray" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    };
+    Some(color)
+}
+
+// Parses descriptors such as "yellow bold" or "black on cyan underline": the first
+// color is the foreground, a color after "on" the background, the rest modifiers
+pub fn parse_style(descriptor: &str) -> Option<Style> {
+    let mut style = Style::default();
+    let mut modifier = Modifier::empty();
+    let mut words = descriptor.split_whitespace().map(|w| w.to_lowercase());
+    while let Some(word) = words.next() {
+        match word.as_str() {
+            "on" => style = style.bg(parse_color(&words.next()?)?),
+            "bold" => modifier |= Modifier::BOLD,
+            "dim" => modifier |= Modifier::DIM,
+            "italic" => modifier |= Modifier::ITALIC,
+            "underline" | "underlined" => modifier |= Modifier::UNDERLINED,
+            "reverse" | "reversed" => modifier |= Modifier::REVERSED,
+            color => style = style.fg(parse_color(color)?),
+        }
+    }
+    Some(style.modifier(modifier))
+}
+
+fn draw_branch_table<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    table: &mut BranchTable,
+    flash: bool,
+) {
+    let mut title = String::from(match table.order {
+        ListOrder::Loaded => "Recent branches",
+        ListOrder::Name => "Recent branches by name",
+        ListOrder::Author => "Recent branches by author",
+    });
+    if !table.filter().is_empty() {
+        title = format!("{title} matching '{}'", table.filter());
+    }
+    // A red border briefly signals a key press that did nothing
+    let border_style = if flash {
+        Style::default().fg(Color::Red)
+    } else {
+        Style::default()
+    };
+    let selected_style = table
+        .options
+        .highlight_style
+        .unwrap_or_else(default_highlight_style);
+    let normal_style = Style::default().fg(Color::White);
+    let two_columns = area.width >= TWO_COLUMN_MIN_WIDTH;
+    let table_width = if two_columns {
+        area.width / 2
+    } else {
+        area.width
+    };
+    let widths = column_widths(table.columns(), table_width, table.name_width);
+
+    if !two_columns {
+        table.column_height = None;
+        // Borders, header and header gap take up four rows
+        table.page_size = (area.height.saturating_sub(4) as usize / ROWS_PER_RECORD).max(1);
+        let t = Table::new(&table.header, &table.items)
+            .links(1, &table.links)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(border_style)
+                    .title(&title),
+            )
+            .style(normal_style)
+            .highlight_style(selected_style)
+            .highlight_symbol(">> ")
+            .widths(&widths);
+        f.render_stateful_widget(t, area, &mut table.state);
+        table.drawn_links.extend(table.state.take_links());
+        let first_row = table.state.offset();
+        table.row_areas.push((rows_area(area), first_row));
+        return;
+    }
+
+    // Two columns are paged together: the left column shows the first half of the
+    // page and the right column continues where the left one ends
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+    // Borders, header and header gap take up four rows
+    let height = (columns[0].height.saturating_sub(4) as usize / ROWS_PER_RECORD).max(1);
+    table.column_height = Some(height);
+    table.page_size = 2 * height;
+    let page_start = table.selected.unwrap_or(0) / (2 * height) * (2 * height);
+
+    for (c, rect) in columns.iter().enumerate() {
+        let start = (page_start + c * height).min(table.visible);
+        let end = (start + height).min(table.visible);
+        let range = table.rows_between(start, end);
+        let first_row = range.start;
+        let rows = &table.items[range.clone()];
+        let links = &table.links[range];
+        let mut state = TableState::default();
+        state.select(
+            table
+                .selected
+                .filter(|i| (start..end).contains(i))
+                .and_then(|i| table.record_rows.get(i))
+                .map(|row| row - first_row),
+        );
+        let title = if c == 0 { title.as_str() } else { "" };
+        let t = Table::new(&table.header, rows)
+            .links(1, links)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(border_style)
+                    .title(title),
+            )
+            .style(normal_style)
+            .highlight_style(selected_style)
+            .highlight_symbol(">> ")
+            .widths(&widths);
+        f.render_stateful_widget(t, *rect, &mut state);
+        table.drawn_links.extend(state.take_links());
+        table
+            .row_areas
+            .push((rows_area(*rect), first_row + state.offset()));
+    }
+}
+
+// Where a bordered table draws its rows, below the header and the gap after it
+fn rows_area(area: Rect) -> Rect {
+    let inner = Block::default().borders(Borders::ALL).inner(area);
+    Rect::new(
+        inner.x,
+        inner.y + 2,
+        inner.width,
+        inner.height.saturating_sub(2),
+    )
+}
+
+// How a key from the config is shown in the help
+fn key_label(c: char) -> String {
+    match c {
+        '\n' => String::from("Enter"),
+        '\t' => String::from("Tab"),
+        ' ' => String::from("Space"),
+        c => c.to_string(),
+    }
+}
+
+// Built-in keys, skipping letters that config has bound to a branch instead
+fn help_lines(config: &Config) -> Vec<String> {
+    let keys = &config.keys;
+    let builtin: Vec<(String, &str, Option<char>)> = vec![
+        (
+            format!("Up / {}", key_label(keys.up)),
+            "previous branch",
+            Some(keys.up),
+        ),
+        (
+            format!("Down / {}", key_label(keys.down)),
+            "next branch",
+            Some(keys.down),
+        ),
+        ("Left / Right".into(), "move between columns", None),
+        (
+            key_label(keys.select),
+            "check out the highlighted branch",
+            Some(keys.select),
+        ),
+        ("^".into(), "jump to the default branch", Some('^')),
+        (
+            "g c / g d / g p".into(),
+            "jump to the current, default or previous branch",
+            Some('g'),
+        ),
+        (
+            key_label(keys.filter),
+            "filter by name or summary",
+            Some(keys.filter),
+        ),
+        (
+            "g g / G, Home / End".into(),
+            "jump to the first or last branch",
+            Some('G'),
+        ),
+        ("Page Up / Page Down".into(), "move a screenful", None),
+        (
+            "S".into(),
+            "sort by name, by author or as loaded",
+            Some('S'),
+        ),
+        ("T".into(), "show exact commit times or ages", Some('T')),
+        ("M".into(), "show more branches", Some('M')),
+        ("Ctrl-R".into(), "refresh the branch list", None),
+        ("R".into(), "show or hide remote branches", Some('R')),
+        ("E".into(), "edit the branch description", Some('E')),
+        (
+            "+".into(),
+            "list the branches at the same commit",
+            Some('+'),
+        ),
+        (
+            format!("{} then y", key_label(keys.delete)),
+            "delete the branch",
+            Some(keys.delete),
+        ),
+        ("A".into(), "only show branches by one author", Some('A')),
+        ("I".into(), "show or hide the commit details", Some('I')),
+        ("Tab".into(), "show or hide columns", None),
+        ("?".into(), "show this help", Some('?')),
+        (
+            format!("{} / Esc", key_label(keys.quit)),
+            "quit",
+            Some(keys.quit),
+        ),
+    ];
+    let mut lines: Vec<String> = builtin
+        .iter()
+        .filter(|(_, _, c)| !c.is_some_and(|c| config.keybind_branch.contains_key(&c)))
+        .map(|(keys, action, _)| format!("{keys:<20} {action}"))
+        .collect();
+    let mut bound: Vec<_> = config.keybind_branch.iter().collect();
+    bound.sort();
+    for (c, name) in bound {
+        lines.push(format!("{:<20} check out {}", c, name));
+    }
+    let taken = shadowed_letters(config);
+    let except = if taken.is_empty() {
+        String::new()
+    } else {
+        let taken: Vec<String> = taken.iter().map(char::to_string).collect();
+        format!(" (not {})", taken.join(", "))
+    };
+    lines.push(format!(
+        "{:<20} jump to a branch by name{except}",
+        "Other letters"
+    ));
+    lines
+}
+
+// Lower-case letters and digits that run a command instead of starting a jump by
+// name. Other built-in commands use capitals, and g only jumps when followed by one
+// of its targets.
+fn shadowed_letters(config: &Config) -> Vec<char> {
+    let keys = &config.keys;
+    let mut letters: Vec<char> = [keys.up, keys.down, keys.quit, keys.delete]
+        .iter()
+        .copied()
+        .chain(config.keybind_branch.keys().copied())
+        .filter(|c| c.is_lowercase() || c.is_ascii_digit())
+        .collect();
+    letters.sort();
+    letters.dedup();
+    letters
+}
+
+fn draw_help<B: Backend>(f: &mut Frame<B>, area: Rect, config: &Config) {
+    let lines = help_lines(config);
+    let width = (lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 4).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let text = [Text::raw(lines.join("\n"))];
+    let paragraph = Paragraph::new(text.iter()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Keys (press any key to close)"),
+    );
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+// The highlighted branch's commit, laid out much like `git show` does
+fn details_text(record: Option<&BranchRecord>) -> String {
+    let record = match record {
+        Some(record) => record,
+        None => return String::from("No branch selected"),
+    };
+    format!(
+        "commit {}\nAuthor: {} <{}>\nDate:   {}\n\n{}",
+        record.commit_sha,
+        record.author_name,
+        record.author_email,
+        record.commit_time().format("%a %b %e %H:%M:%S %Y %z"),
+        record.message,
+    )
+}
+
+fn draw_details<B: Backend>(f: &mut Frame<B>, area: Rect, text: &str) {
+    let text = [Text::raw(text)];
+    let paragraph = Paragraph::new(text.iter())
+        .block(Block::default().borders(Borders::ALL).title("Details"))
+        .wrap(true);
+    f.render_widget(paragraph, area);
+}
+
+// Authors of the branches, most recent first, for narrowing the list to one of them
+struct AuthorMenu {
+    // Email, name and number of branches; the first entry lists all authors again
+    authors: Vec<(String, String, usize)>,
+    state: ListState,
+}
+
+impl AuthorMenu {
+    fn new(records: &[BranchRecord], current: Option<&str>) -> AuthorMenu {
+        let mut authors: Vec<(String, String, usize)> = vec![];
+        for record in records {
+            match authors
+                .iter_mut()
+                .find(|(email, _, _)| *email == record.author_email)
+            {
+                Some((_, _, count)) => *count += 1,
+                None => authors.push((record.author_email.clone(), record.author_name.clone(), 1)),
+            }
+        }
+        let selected = current
+            .and_then(|current| authors.iter().position(|(email, _, _)| email == current))
+            .map_or(0, |i| i + 1);
+        let mut state = ListState::default();
+        state.select(Some(selected));
+        AuthorMenu { authors, state }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let all = std::iter::once(String::from("All authors"));
+        all.chain(
+            self.authors
+                .iter()
+                .map(|(email, name, count)| format!("{name} <{email}> ({count})")),
+        )
+        .collect()
+    }
+
+    fn move_by(&mut self, delta: isize) -> bool {
+        let current = self.state.selected().unwrap_or(0) as isize;
+        let next = current + delta;
+        if next < 0 || next > self.authors.len() as isize {
+            return false;
+        }
+        self.state.select(Some(next as usize));
+        true
+    }
+
+    // The chosen author's email and name, None for all authors
+    fn chosen(&self) -> Option<(String, String)> {
+        let i = self.state.selected()?.checked_sub(1)?;
+        self.authors
+            .get(i)
+            .map(|(email, name, _)| (email.clone(), name.clone()))
+    }
+}
+
+fn draw_author_menu<B: Backend>(f: &mut Frame<B>, area: Rect, menu: &mut AuthorMenu) {
+    let lines = menu.lines();
+    let title = "Show branches by";
+    // Room for the borders and the highlight symbol
+    let content_width = lines.iter().map(|l| l.width() + 3).max().unwrap_or(0);
+    let width = (content_width.max(title.width()) as u16 + 4).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let list = List::new(lines.into_iter().map(Text::raw))
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(default_highlight_style())
+        .highlight_symbol(">> ");
+    f.render_widget(Clear, popup);
+    f.render_stateful_widget(list, popup, &mut menu.state);
+}
+
+fn column_menu_text(enabled: &[Column]) -> String {
+    let entries: Vec<String> = OPTIONAL_COLUMNS
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let mark = if enabled.contains(c) { "x" } else { " " };
+            format!("{} [{}] {}", i + 1, mark, c.title())
+        })
+        .collect();
+    format!("Toggle columns: {}  (Tab to close)", entries.join("  "))
+}
+
+const QUICK_SELECT_TIMEOUT: Duration = Duration::from_millis(1000);
+const PREVIEW_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Deletes the highlighted branch and drops it from the list, returning the outcome to
+// show in the footer
+fn delete_selected(
+    table: &mut BranchTable,
+    delete_branch: &dyn Fn(&BranchRecord) -> Result<(), Error>,
+) -> Option<String> {
+    let record = table.selected_record()?;
+    let name = record.name.clone();
+    let sha = record.abbreviated_sha(table.options.sha_length).to_string();
+    match delete_branch(record) {
+        Ok(()) => {
+            table.remove_selected();
+            Some(format!("Deleted branch '{name}' (was {sha})"))
+        }
+        Err(e) => Some(format!("Failed to delete '{name}': {e}")),
+    }
+}
+
+// Stores the highlighted branch's description, returning the error to show if any
+fn save_description(
+    table: &mut BranchTable,
+    set_description: &dyn Fn(&BranchRecord, &str) -> Result<(), Error>,
+    description: &str,
+) -> Option<String> {
+    let description = description.trim();
+    let result = match table.selected_record() {
+        Some(record) => set_description(record, description),
+        None => Ok(()),
+    };
+    match result {
+        Ok(()) => {
+            table.set_selected_description(description);
+            None
+        }
+        Err(e) => Some(format!("Failed to save description: {e}")),
+    }
+}
+
+// Panic messages are held back while the picker owns the terminal, as they would be
+// printed on the alternate screen and vanish with it. Unwinding drops the terminal,
+// which shows the cursor, leaves raw mode and the alternate screen, and then the
+// guard prints them.
+static HELD_PANICS: Mutex<Option<Vec<String>>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+struct PanicGuard;
+
+impl PanicGuard {
+    fn new() -> PanicGuard {
+        PANIC_HOOK.call_once(|| {
+            let default_hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                let mut held = HELD_PANICS.lock().unwrap_or_else(|e| e.into_inner());
+                match held.as_mut() {
+                    Some(messages) => {
+                        let backtrace = Backtrace::capture();
+                        messages.push(match backtrace.status() {
+                            BacktraceStatus::Captured => format!("{info}\n{backtrace}"),
+                            _ => info.to_string(),
+                        });
+                    }
+                    None => {
+                        drop(held);
+                        default_hook(info);
+                    }
+                }
+            }));
+        });
+        *HELD_PANICS.lock().unwrap_or_else(|e| e.into_inner()) = Some(vec![]);
+        PanicGuard
+    }
+}
+
+impl Drop for PanicGuard {
+    fn drop(&mut self) {
+        let held = HELD_PANICS.lock().unwrap_or_else(|e| e.into_inner()).take();
+        // Leaving the alternate screen is only buffered until now
+        let _ = io::stdout().flush();
+        for message in held.into_iter().flatten() {
+            eprintln!("{message}");
+        }
+    }
+}
+
+// Prints the linked text again wrapped in OSC 8 escapes, which terminals without
+// support ignore
+fn write_links<B: Backend + Write>(backend: &mut B, links: &[Link]) -> io::Result<()> {
+    if links.is_empty() {
+        return Ok(());
+    }
+    for link in links {
+        write!(backend, "\x1b]8;;{}\x1b\\", link.url)?;
+        backend.draw(link.cells.iter().map(|(x, cell)| (*x, link.y, cell)))?;
+        write!(backend, "\x1b]8;;\x1b\\")?;
+    }
+    Write::flush(backend)
+}
+
+type PickerTerminal =
+    Terminal<TermionBackend<AlternateScreen<MouseTerminal<RawTerminal<io::Stdout>>>>>;
+
+fn open_terminal() -> io::Result<PickerTerminal> {
     let stdout = io::stdout().into_raw_mode()?;
+    let stdout = MouseTerminal::from(stdout);
     let stdout = AlternateScreen::from(stdout);
     let backend = TermionBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     terminal.hide_cursor()?;
+    Ok(terminal)
+}
+
+// Lets the text be written in $VISUAL or $EDITOR (vi by default) and returns what was
+// saved there, or None if the editor failed
+fn edit_in_editor(initial: &str) -> io::Result<Option<String>> {
+    let path = std::env::temp_dir().join(format!("git-checkout-recent-{}.txt", std::process::id()));
+    std::fs::write(&path, initial)?;
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    // Through the shell, as editors are often set with arguments like "code --wait"
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg("sh")
+        .arg(&path)
+        .status();
+    let text = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    match status {
+        Ok(status) if status.success() => Ok(Some(text?.trim_end().to_string())),
+        _ => Ok(None),
+    }
+}
 
-    let events = Events::new();
+pub fn render_branch_selection<'a>(
+    table: &'a mut BranchTable,
+    config: &Config,
+    load_records: &dyn Fn(bool) -> Vec<BranchRecord>,
+    load_details: &dyn Fn(&mut BranchRecord),
+    set_description: &dyn Fn(&BranchRecord, &str) -> Result<(), Error>,
+    delete_branch: &dyn Fn(&BranchRecord) -> Result<(), Error>,
+) -> Result<Option<&'a BranchRecord>, Error> {
+    // Declared first so that it is dropped after the terminal
+    let _panic_guard = PanicGuard::new();
+    let mut terminal = open_terminal()?;
+
+    // q does not always quit (e.g. it only closes the help), so keep reading after it
+    // 0 would have the input thread spin instead of sleeping
+    let defaults = EventConfig::default();
+    let mut events = Events::with_config(EventConfig {
+        poll_interval: config
+            .poll_interval_ms
+            .filter(|&ms| ms > 0)
+            .map_or(defaults.poll_interval, Duration::from_millis),
+        tick_rate: config
+            .tick_interval_ms
+            .filter(|&ms| ms > 0)
+            .map_or(defaults.tick_rate, Duration::from_millis),
+        ..defaults
+    });
+    events.disable_exit_key();
 
     table.init();
 
+    // Type-ahead buffer for jumping to a branch by the first letters of its name
+    let mut quick_select = String::new();
+    let mut quick_select_time = Instant::now();
+
+    // Transient feedback shown below the table until the next key press
+    let mut message: Option<String> = None;
+
+    // Set when a key press had no effect, so the next frame can flash or ring the bell
+    let mut rejected = false;
+
+    // Whether the key binding help is shown over the table
+    let mut show_help = false;
+
+    // Whether the column toggle menu is open
+    let mut column_menu = false;
+
+    // Whether the highlighted branch's full commit is shown below the table
+    let mut show_details = false;
+
+    // Whether typed characters go into the filter rather than selecting branches
+    let mut filter_input = false;
+
+    // Text typed so far while editing the highlighted branch's description
+    let mut description_input: Option<String> = None;
+
+    // Set when the description is to be written in $EDITOR instead, which needs the
+    // terminal to itself for the duration
+    let mut open_editor = false;
+
+    // For the idle timeout
+    let mut last_input = Instant::now();
+
+    // Keys from the config for the main actions
+    let up_key = Key::Char(config.keys.up);
+    let down_key = Key::Char(config.keys.down);
+    let select_key = Key::Char(config.keys.select);
+    let quit_key = Key::Char(config.keys.quit);
+    let delete_key = Key::Char(config.keys.delete);
+    let filter_key = Key::Char(config.keys.filter);
+
+    // Dates return to this format when exact times are toggled off again
+    let date_format = table.options.date_format;
+
+    // Whether remote-tracking branches are listed along with the local ones
+    let mut include_remotes = table.options.include_remotes;
+
+    // Set after g, which starts a two-key jump such as "g c" for the current branch
+    let mut go_to_pending = false;
+
+    // Set after D, until y confirms deleting the highlighted branch
+    let mut delete_pending = false;
+
+    // Email and name of the author the list is narrowed to, and the menu choosing it
+    let mut author: Option<(String, String)> = None;
+    let mut author_menu: Option<AuthorMenu> = None;
+    // Authors are only known once details are loaded, so filtering loads them all
+    let load = |include_remotes: bool, author: Option<&str>| {
+        let mut records = load_records(include_remotes);
+        if let Some(email) = author {
+            for record in records.iter_mut() {
+                load_details(record);
+            }
+            records.retain(|r| r.author_email == email);
+        }
+        records
+    };
+
+    // Latest output of the preview command and the branch it was run for. The
+    // output stays up until the worker has one for the new selection.
+    let preview_worker = table
+        .options
+        .preview_command
+        .clone()
+        .map(|command| PreviewWorker::new(command, PREVIEW_TIMEOUT));
+    let mut preview: Option<(String, String)> = None;
+    let mut requested: Option<String> = None;
+
     // Input
     loop {
+        if open_editor {
+            open_editor = false;
+            let initial = description_input
+                .take()
+                .or_else(|| table.selected_record()?.description.clone())
+                .unwrap_or_default();
+            events.pause();
+            drop(terminal);
+            let edited = edit_in_editor(&initial);
+            terminal = open_terminal()?;
+            events.resume();
+            message = match edited {
+                Ok(Some(text)) => save_description(table, set_description, &text),
+                Ok(None) => Some(String::from("Editor failed, description unchanged")),
+                Err(e) => Some(format!("Failed to run the editor: {e}")),
+            };
+        }
+
+        if let (Some(worker), Some(record)) = (&preview_worker, table.selected_record()) {
+            if requested.as_deref() != Some(record.name.as_str()) {
+                worker.request(&record.name);
+                requested = Some(record.name.clone());
+            }
+            if let Some(result) = worker.latest() {
+                preview = Some(result);
+            }
+            if preview.is_none() {
+                preview = Some((String::new(), String::from("Loading…")));
+            }
+        }
+
+        table.load_visible_details(load_details);
+        let flash = rejected && !config.bell;
+        if rejected && config.bell {
+            write!(terminal.backend_mut(), "\x07")?;
+            Write::flush(terminal.backend_mut())?;
+        }
+        rejected = false;
         terminal.draw(|mut f| {
+            let mut size = f.size();
+            if let Some(max_width) = table.options.max_width {
+                if size.width > max_width {
+                    size.x += (size.width - max_width) / 2;
+                    size.width = max_width;
+                }
+            }
+            let stats = if table.options.stat {
+                Some(branch_stats(&table.records, table.options.now()))
+            } else {
+                None
+            };
+            let footer_height = if stats.is_some() { 2 } else { 1 };
+            table.row_areas.clear();
+            table.drawn_links.clear();
+            if size.width < MIN_TABLE_WIDTH || size.height < MIN_TABLE_HEIGHT + footer_height {
+                let text = [Text::raw("Terminal too small")];
+                f.render_widget(Paragraph::new(text.iter()), size);
+                return;
+            }
+            // Small terminals drop the margin rather than squeezing the table
+            let margin = if size.width >= MIN_TABLE_WIDTH + 2 * MARGIN
+                && size.height >= MIN_TABLE_HEIGHT + footer_height + 2 * MARGIN
+            {
+                MARGIN
+            } else {
+                0
+            };
             let rects = Layout::default()
-                .constraints([Constraint::Percentage(100)].as_ref())
-                .margin(5)
-                .split(f.size());
-
-            let selected_style = Style::default().fg(Color::Yellow).modifier(Modifier::BOLD);
-            let normal_style = Style::default().fg(Color::White);
-            let rows = table
-                .items
-                .iter()
-                .map(|i| Row::StyledData(i.iter(), normal_style));
-            let t = Table::new(table.header.iter(), rows)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title("Recent branches"),
-                )
-                .highlight_style(selected_style)
-                .highlight_symbol(">> ")
-                .widths(&[Constraint::Percentage(40), Constraint::Percentage(60)]);
-            f.render_stateful_widget(t, rects[0], &mut table.state);
+                .constraints([Constraint::Min(0), Constraint::Length(footer_height)].as_ref())
+                .margin(margin)
+                .split(size);
+
+            // The details take at most half the height, below the table and any preview
+            let mut main_area = rects[0];
+            if show_details {
+                let text = details_text(table.selected_record());
+                let height = (text.lines().count() as u16 + 2).min(main_area.height / 2);
+                let areas = Layout::default()
+                    .constraints([Constraint::Min(0), Constraint::Length(height)].as_ref())
+                    .split(main_area);
+                main_area = areas[0];
+                draw_details(&mut f, areas[1], &text);
+            }
+
+            match &preview {
+                Some((_, output)) if main_area.width >= 2 * MIN_TABLE_WIDTH => {
+                    let panes = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints(
+                            [Constraint::Percentage(50), Constraint::Percentage(50)].as_ref(),
+                        )
+                        .split(main_area);
+                    draw_branch_table(&mut f, panes[0], table, flash);
+                    let text = [Text::raw(output.as_str())];
+                    let paragraph = Paragraph::new(text.iter())
+                        .block(Block::default().borders(Borders::ALL).title("Preview"));
+                    f.render_widget(paragraph, panes[1]);
+                }
+                _ => draw_branch_table(&mut f, main_area, table, flash),
+            }
+
+            let footer = match &message {
+                _ if filter_input => Some(format!(
+                    "Filter: {}▏ (Enter to keep, Esc to clear)",
+                    table.filter()
+                )),
+                _ if description_input.is_some() => Some(format!(
+                    "Description: {}▏ (Enter to save, empty to clear, Ctrl-E for $EDITOR, Esc to cancel)",
+                    description_input.as_deref().unwrap_or_default()
+                )),
+                Some(message) => Some(message.clone()),
+                None if column_menu => Some(column_menu_text(table.columns())),
+                None if table.hidden_count() > 0 => Some(format!(
+                    "{} more branches, press M to show more",
+                    table.hidden_count()
+                )),
+                None => None,
+            };
+            // Ahead/behind counts and remote branches are only as fresh as the last fetch
+            let remote_aware = include_remotes
+                || table
+                    .columns()
+                    .iter()
+                    .any(|c| matches!(c, Column::Remote | Column::AheadBehind));
+            let fetch_status = remote_aware.then(|| match table.options.last_fetch_seconds {
+                Some(fetched) => format!(
+                    "remotes fetched {} ago",
+                    format_compact_age(fetched, table.options.now())
+                ),
+                None => String::from("remotes never fetched"),
+            });
+            let footer = match (footer, author.as_ref()) {
+                (Some(footer), Some((_, name))) => Some(format!("{footer}  ·  by {name}")),
+                (None, Some((_, name))) => Some(format!("Branches by {name}, press A to change")),
+                (footer, None) => footer,
+            };
+            let footer = match (footer, fetch_status) {
+                (Some(footer), Some(status)) => Some(format!("{footer}  ·  {status}")),
+                (footer, status) => footer.or(status),
+            };
+            if show_help {
+                draw_help(&mut f, rects[0], config);
+            }
+            if let Some(menu) = &mut author_menu {
+                draw_author_menu(&mut f, rects[0], menu);
+            }
+
+            let lines: Vec<String> = stats.into_iter().chain(footer).collect();
+            if !lines.is_empty() {
+                let text = [Text::raw(lines.join("\n"))];
+                f.render_widget(Paragraph::new(text.iter()), rects[1]);
+            }
+            f.render_widget(UncoveredLinks(&mut table.drawn_links), size);
         })?;
+        write_links(terminal.backend_mut(), &table.drawn_links)?;
 
-        if let Event::Input(key) = events.next()? {
+        // Ticks only redraw while something can change without a key press, so an
+        // idle picker stays asleep
+        let waiting_for_preview = preview_worker.is_some()
+            && preview.as_ref().map(|(name, _)| name) != requested.as_ref();
+        let size = terminal.size()?;
+        let idle_timed_out = || {
+            table
+                .options
+                .idle_timeout
+                .is_some_and(|timeout| last_input.elapsed() >= timeout)
+        };
+        let event = loop {
+            let event = events
+                .next()
+                .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e))?;
+            match event {
+                Event::Tick if idle_timed_out() => break None,
+                Event::Tick if !flash && !waiting_for_preview && terminal.size()? == size => {}
+                event => break Some(event),
+            }
+        };
+        let event = match event {
+            Some(event) => event,
+            // Left alone for too long, give up as if q was pressed
+            None => {
+                table.deselect();
+                break;
+            }
+        };
+        // A click selects the branch under it, and clicking the selected branch again
+        // checks it out like Enter. Clicks are ignored while a prompt or menu is open.
+        let modal = show_help
+            || column_menu
+            || filter_input
+            || description_input.is_some()
+            || author_menu.is_some();
+        let key = match event {
+            Event::Input(key) => Some(key),
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, x, y)) if !modal => {
+                // Terminal coordinates start at 1
+                match table.record_at(x.saturating_sub(1), y.saturating_sub(1)) {
+                    Some(i) if table.selected == Some(i) => Some(select_key),
+                    Some(i) => {
+                        last_input = Instant::now();
+                        quick_select.clear();
+                        go_to_pending = false;
+                        delete_pending = false;
+                        message = None;
+                        table.select(Some(i));
+                        None
+                    }
+                    None => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(key) = key {
+            last_input = Instant::now();
+            if quick_select_time.elapsed() > QUICK_SELECT_TIMEOUT {
+                quick_select.clear();
+            }
+            message = None;
+            let go_to = std::mem::take(&mut go_to_pending);
+            let delete = std::mem::take(&mut delete_pending);
             match key {
-                Key::Char('q') | Key::Char('Q') | Key::Esc => {
+                _ if show_help => show_help = false,
+                Key::Char('y') if delete => message = delete_selected(table, delete_branch),
+                _ if delete => message = Some(String::from("Not deleted")),
+                Key::Esc if description_input.is_some() => description_input = None,
+                Key::Char('\n') if description_input.is_some() => {
+                    let description = description_input.take().unwrap_or_default();
+                    message = save_description(table, set_description, &description);
+                }
+                Key::Ctrl('e') if description_input.is_some() => open_editor = true,
+                Key::Backspace if description_input.is_some() => {
+                    if let Some(input) = &mut description_input {
+                        input.pop();
+                    }
+                }
+                Key::Char(c) if description_input.is_some() => {
+                    if let Some(input) = &mut description_input {
+                        input.push(c);
+                    }
+                }
+                _ if description_input.is_some() => {}
+                Key::Esc if filter_input => {
+                    filter_input = false;
+                    table.set_filter("", load_details);
+                }
+                Key::Char('\n') if filter_input => filter_input = false,
+                Key::Backspace if filter_input => {
+                    let mut query = table.filter().to_string();
+                    query.pop();
+                    table.set_filter(&query, load_details);
+                }
+                Key::Char(c) if filter_input && !c.is_control() => {
+                    let query = format!("{}{c}", table.filter());
+                    table.set_filter(&query, load_details);
+                    rejected = table.selected_record().is_none();
+                }
+                Key::Esc | Key::Char('A') if author_menu.is_some() => author_menu = None,
+                key if author_menu.is_some() && (key == Key::Up || key == up_key) => {
+                    rejected = !author_menu.as_mut().is_some_and(|menu| menu.move_by(-1));
+                }
+                key if author_menu.is_some() && (key == Key::Down || key == down_key) => {
+                    rejected = !author_menu.as_mut().is_some_and(|menu| menu.move_by(1));
+                }
+                Key::Char('\n') if author_menu.is_some() => {
+                    author = author_menu.take().and_then(|menu| menu.chosen());
+                    let email = author.as_ref().map(|(email, _)| email.as_str());
+                    table.set_records(load(include_remotes, email), load_details);
+                }
+                _ if author_menu.is_some() => {}
+                Key::Char('\t') | Key::Esc if column_menu => {
+                    column_menu = false;
+                    if table.options.save_columns {
+                        if let Err(e) = save_columns(table.columns()) {
+                            message = Some(format!("Failed to save columns: {e}"));
+                        }
+                    }
+                }
+                Key::Char('\t') => column_menu = true,
+                Key::Char(c) if column_menu => {
+                    let column = c
+                        .to_digit(10)
+                        .and_then(|d| OPTIONAL_COLUMNS.get((d as usize).wrapping_sub(1)));
+                    if let Some(column) = column {
+                        table.toggle_column(*column);
+                    }
+                }
+                Key::Esc if go_to => {}
+                Key::Char(c) if go_to => {
+                    let (found, what) = match c {
+                        'c' => (table.select_where(|r| r.is_current_branch), "current"),
+                        'd' => (table.select_where(|r| r.is_default), "default"),
+                        'p' => (table.select_where(|r| r.is_previous), "previous"),
+                        // As in vim, gg goes to the top
+                        'g' => (table.first(), "first"),
+                        _ => {
+                            // Not a jump after all, just a name starting with g
+                            quick_select = format!("g{c}");
+                            quick_select_time = Instant::now();
+                            table.select_by_prefix(&quick_select);
+                            (true, "")
+                        }
+                    };
+                    if !found {
+                        message = Some(format!("No {what} branch in the list"));
+                        rejected = true;
+                    }
+                }
+                Key::Char(c) if !quick_select.is_empty() && Key::Char(c) != select_key => {
+                    quick_select.push(c);
+                    quick_select_time = Instant::now();
+                    table.select_by_prefix(&quick_select);
+                }
+                Key::Esc if !table.filter().is_empty() => table.set_filter("", load_details),
+                key if key == quit_key || key == Key::Char('Q') || key == Key::Esc => {
                     table.deselect();
                     break;
                 }
-                Key::Down | Key::Char('j') => {
-                    table.next();
+                key if key == Key::Down || key == down_key => {
+                    quick_select.clear();
+                    rejected = !table.next();
                 }
-                Key::Up | Key::Char('k') => {
-                    table.previous();
+                key if key == Key::Up || key == up_key => {
+                    quick_select.clear();
+                    rejected = !table.previous();
                 }
-                Key::Char('\n') => {
-                    break;
+                Key::Left => {
+                    quick_select.clear();
+                    rejected = !table.left();
+                }
+                Key::Right => {
+                    quick_select.clear();
+                    rejected = !table.right();
+                }
+                key if key == select_key => {
+                    let current = table.selected_record().filter(|r| r.is_current_branch);
+                    match current {
+                        Some(r) if table.options.keep_open_on_current => {
+                            message = Some(format!("Already on '{}'", r.name));
+                            rejected = true;
+                        }
+                        _ => break,
+                    }
+                }
+                key if key == delete_key => {
+                    quick_select.clear();
+                    match table.selected_record() {
+                        Some(r) if r.is_current_branch => {
+                            message =
+                                Some(format!("Cannot delete '{}', it is checked out", r.name));
+                            rejected = true;
+                        }
+                        Some(r) if is_protected(&r.name, &config.protected_branches) => {
+                            message = Some(format!("Cannot delete '{}', it is protected", r.name));
+                            rejected = true;
+                        }
+                        Some(r) if r.ref_name.starts_with("refs/heads/") => {
+                            delete_pending = true;
+                            // Deleting loses those commits unless they are on another branch
+                            message = Some(match r.ahead_behind {
+                                Some((ahead, _)) if ahead > 0 => format!(
+                                    "'{}' has {ahead} unpushed commit(s)! Delete it anyway? \
+                                     Press y to confirm",
+                                    r.name
+                                ),
+                                _ => format!("Delete branch '{}'? Press y to confirm", r.name),
+                            });
+                        }
+                        _ => rejected = true,
+                    }
+                }
+                // By default /, which no branch name starts with
+                key if key == filter_key => {
+                    quick_select.clear();
+                    filter_input = true;
+                }
+                Key::Char(c) if config.keybind_branch.contains_key(&c) => {
+                    let name = &config.keybind_branch[&c];
+                    if table.select_by_name(name) {
+                        break;
+                    }
+                    message = Some(format!("No branch '{name}' in the list"));
+                    rejected = true;
+                }
+                Key::Char('g') => {
+                    go_to_pending = true;
+                    message = Some(String::from(
+                        "Go to: c current, d default, p previous, g first",
+                    ));
+                }
+                Key::PageDown => {
+                    quick_select.clear();
+                    rejected = !table.page_down();
+                }
+                Key::PageUp => {
+                    quick_select.clear();
+                    rejected = !table.page_up();
+                }
+                Key::Home => {
+                    quick_select.clear();
+                    rejected = !table.first();
+                }
+                Key::End => {
+                    quick_select.clear();
+                    rejected = !table.last();
+                }
+                Key::Char('G') => {
+                    quick_select.clear();
+                    rejected = !table.last();
+                }
+                Key::Char('?') => show_help = true,
+                Key::Char('^') => {
+                    let found = table.select_default();
+                    message = (!found).then(|| String::from("No default branch in the list"));
+                    rejected = !found;
+                }
+                Key::Char('M') => {
+                    quick_select.clear();
+                    rejected = !table.show_more();
+                }
+                Key::Ctrl('r') => {
+                    quick_select.clear();
+                    let email = author.as_ref().map(|(email, _)| email.as_str());
+                    table.set_records(load(include_remotes, email), load_details);
+                    message = Some(String::from("Refreshed branch list"));
+                }
+                Key::Char('+') => rejected = !table.expand_selected(),
+                Key::Char('S') => {
+                    quick_select.clear();
+                    table.cycle_order(load_details);
+                }
+                Key::Char('I') => show_details = !show_details,
+                Key::Char('T') => {
+                    quick_select.clear();
+                    table.toggle_absolute_dates(date_format);
+                }
+                Key::Char('A') => {
+                    quick_select.clear();
+                    let mut records = load_records(include_remotes);
+                    for record in records.iter_mut() {
+                        load_details(record);
+                    }
+                    let current = author.as_ref().map(|(email, _)| email.as_str());
+                    author_menu = Some(AuthorMenu::new(&records, current));
+                }
+                Key::Char('E') => {
+                    quick_select.clear();
+                    match table.selected_record() {
+                        Some(record) if !record.ref_name.starts_with("refs/heads/") => {
+                            rejected = true
+                        }
+                        Some(_) if config.external_editor => open_editor = true,
+                        Some(record) => {
+                            description_input =
+                                Some(record.description.clone().unwrap_or_default());
+                        }
+                        _ => rejected = true,
+                    }
+                }
+                Key::Char('R') => {
+                    quick_select.clear();
+                    include_remotes = !include_remotes;
+                    let email = author.as_ref().map(|(email, _)| email.as_str());
+                    table.set_records(load(include_remotes, email), load_details);
+                    message = Some(String::from(if include_remotes {
+                        "Showing remote branches"
+                    } else {
+                        "Hiding remote branches"
+                    }));
+                }
+                Key::Char(c) if c.is_alphanumeric() || c == '/' || c == '-' || c == '_' => {
+                    quick_select.push(c);
+                    quick_select_time = Instant::now();
+                    table.select_by_prefix(&quick_select);
                 }
                 _ => {}
             }
@@ -159,3 +2146,306 @@ pub fn render_branch_selection<'a>(
 
     Ok(table.selected_record())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record;
+    use chrono::TimeZone;
+    use std::ops::Range;
+    use tui::backend::TestBackend;
+
+    fn default_options() -> DisplayOptions {
+        DisplayOptions {
+            columns: DEFAULT_COLUMNS.to_vec(),
+            ..DisplayOptions::default()
+        }
+    }
+
+    fn table_of(names: &[&str]) -> BranchTable {
+        let records = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| record(name, &format!("{i:040}"), 1_600_000_000 - i as i64))
+            .collect();
+        let mut table = BranchTable::new(records, &default_options());
+        table.init();
+        table
+    }
+
+    fn selected(table: &BranchTable) -> Option<&str> {
+        table.selected_record().map(|r| r.name.as_str())
+    }
+
+    #[test]
+    fn one_branch_stays_selected() {
+        let mut table = table_of(&["a"]);
+        assert_eq!(selected(&table), Some("a"));
+        assert!(!table.next());
+        assert!(!table.previous());
+        assert_eq!(selected(&table), Some("a"));
+    }
+
+    #[test]
+    fn two_branches_are_both_reachable() {
+        let mut table = table_of(&["a", "b"]);
+        assert!(table.next());
+        assert_eq!(selected(&table), Some("b"));
+        assert!(!table.next());
+        assert_eq!(selected(&table), Some("b"));
+        assert!(table.previous());
+        assert!(!table.previous());
+        assert_eq!(selected(&table), Some("a"));
+    }
+
+    #[test]
+    fn three_branches_step_one_at_a_time() {
+        let mut table = table_of(&["a", "b", "c"]);
+        table.next();
+        assert_eq!(selected(&table), Some("b"));
+        table.next();
+        assert_eq!(selected(&table), Some("c"));
+        table.next();
+        assert_eq!(selected(&table), Some("c"));
+        table.previous();
+        assert_eq!(selected(&table), Some("b"));
+        table.previous();
+        table.previous();
+        assert_eq!(selected(&table), Some("a"));
+    }
+
+    fn row_text(row: &[Cell]) -> String {
+        row.iter()
+            .flatten()
+            .map(|text| match text {
+                Text::Raw(s) | Text::Styled(s, _) => s.as_ref(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn full_shas_are_cut_to_eight_characters() {
+        let records = [record("a", "0123456789abcdef0123456789abcdef01234567", 0)];
+        let (data, _) = get_table_data_from_branch_records(&records, &default_options());
+        let text: String = data.iter().map(|row| row_text(row)).collect();
+        assert!(text.contains("01234567 "));
+        assert!(!text.contains("012345678"));
+    }
+
+    #[test]
+    fn shas_shorter_than_eight_characters_are_shown_whole() {
+        let records = [record("a", "abc", 0), record("b", "", 0)];
+        let options = DisplayOptions {
+            sha_length: Some(12),
+            ..default_options()
+        };
+        for options in [default_options(), options] {
+            let (data, _) = get_table_data_from_branch_records(&records, &options);
+            let text: String = data.iter().map(|row| row_text(row)).collect();
+            assert!(text.contains("abc "));
+        }
+    }
+
+    fn names(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("branch-{i:02}")).collect()
+    }
+
+    fn table_of_count(count: usize) -> BranchTable {
+        let names = names(count);
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        table_of(&names)
+    }
+
+    #[test]
+    fn wrapping_goes_over_every_branch_in_both_directions() {
+        let names = names(5);
+        let records = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| record(name, &format!("{i:040}"), 1_600_000_000 - i as i64))
+            .collect();
+        let options = DisplayOptions {
+            wrap: true,
+            batch_size: 2,
+            ..default_options()
+        };
+        let mut table = BranchTable::new(records, &options);
+        table.init();
+        assert_eq!(table.visible, 2);
+
+        assert!(table.previous());
+        assert_eq!(selected(&table), Some("branch-04"));
+        assert_eq!(table.visible, 5);
+        assert!(table.next());
+        assert_eq!(selected(&table), Some("branch-00"));
+    }
+
+    #[test]
+    fn page_down_and_up_clamp_at_the_ends() {
+        let mut table = table_of_count(25);
+        table.page_size = 10;
+        assert!(table.page_down());
+        assert_eq!(selected(&table), Some("branch-10"));
+        assert!(table.page_down());
+        assert!(table.page_down());
+        assert_eq!(selected(&table), Some("branch-24"));
+        assert!(!table.page_down());
+        assert_eq!(selected(&table), Some("branch-24"));
+
+        assert!(table.page_up());
+        assert_eq!(selected(&table), Some("branch-14"));
+        assert!(table.page_up());
+        assert!(table.page_up());
+        assert_eq!(selected(&table), Some("branch-00"));
+        assert!(!table.page_up());
+    }
+
+    #[test]
+    fn names_starting_with_m_can_be_typed() {
+        let mut table = table_of(&["feature", "main", "master"]);
+        table.select_by_prefix("m");
+        assert_eq!(selected(&table), Some("main"));
+        table.select_by_prefix("mas");
+        assert_eq!(selected(&table), Some("master"));
+
+        let config = Config::default();
+        assert_eq!(shadowed_letters(&config), vec!['j', 'k', 'q']);
+        let help = help_lines(&config);
+        assert!(help.last().unwrap().ends_with("(not j, k, q)"));
+    }
+
+    #[test]
+    fn first_and_last_jump_to_the_ends() {
+        let mut table = table_of_count(5);
+        assert!(!table.first());
+        assert!(table.last());
+        assert_eq!(selected(&table), Some("branch-04"));
+        assert!(!table.last());
+        assert!(table.first());
+        assert_eq!(selected(&table), Some("branch-00"));
+
+        let mut empty = table_of(&[]);
+        assert!(!empty.first());
+        assert!(!empty.last());
+        assert!(!empty.page_down());
+    }
+
+    fn draw(terminal: &mut Terminal<TestBackend>, table: &mut BranchTable) {
+        terminal
+            .draw(|mut f| {
+                let area = f.size();
+                draw_branch_table(&mut f, area, table, false);
+            })
+            .unwrap();
+    }
+
+    // Rows of `items` shown by the last draw, below the borders, header and its gap
+    fn rows_on_screen(terminal: &Terminal<TestBackend>, table: &BranchTable) -> Range<usize> {
+        let height = terminal.size().unwrap().height as usize - 4;
+        let offset = table.state.offset();
+        offset..offset + height
+    }
+
+    #[test]
+    fn scrolling_keeps_the_selection_on_screen() {
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        let mut table = table_of_count(30);
+        draw(&mut terminal, &mut table);
+        for _ in 0..29 {
+            table.next();
+            draw(&mut terminal, &mut table);
+            let row = table.record_rows[table.selected.unwrap()];
+            assert!(rows_on_screen(&terminal, &table).contains(&row));
+        }
+        assert!(table.state.offset() > 0);
+
+        table.page_up();
+        draw(&mut terminal, &mut table);
+        let row = table.record_rows[table.selected.unwrap()];
+        assert!(rows_on_screen(&terminal, &table).contains(&row));
+        table.first();
+        draw(&mut terminal, &mut table);
+        assert_eq!(table.state.offset(), 0);
+    }
+
+    #[test]
+    fn changing_the_list_scrolls_back_to_the_top() {
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        let mut table = table_of_count(30);
+        table.last();
+        draw(&mut terminal, &mut table);
+        assert!(table.state.offset() > 0);
+
+        table.set_filter("branch-0", &|_| {});
+        draw(&mut terminal, &mut table);
+        assert_eq!(table.state.offset(), 0);
+        assert_eq!(selected(&table), Some("branch-00"));
+    }
+
+    #[test]
+    fn filtering_loads_the_summaries_it_matches_on() {
+        let mut table = table_of(&["first", "second"]);
+        for record in table.records.iter_mut() {
+            record.summary = String::new();
+            record.has_details = false;
+        }
+        let load = |r: &mut BranchRecord| {
+            r.summary = format!("Fix {}", r.name);
+            r.has_details = true;
+        };
+
+        table.set_filter("fix second", &load);
+        assert_eq!(selected(&table), Some("second"));
+        assert_eq!(table.records.len(), 1);
+    }
+
+    fn screen_lines(terminal: &Terminal<TestBackend>) -> Vec<String> {
+        let buffer = terminal.backend().buffer();
+        let area = buffer.area();
+        (area.top()..area.bottom())
+            .map(|y| {
+                let line: String = (area.left()..area.right())
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect();
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn table_renders_dates_relative_to_a_fixed_now() {
+        let now = Utc.timestamp(1_600_000_000, 0);
+        let records = vec![
+            record("main", "1111111111", 1_600_000_000 - 2 * 60 * 60),
+            record("topic", "2222222222", 1_600_000_000 - 3 * 24 * 60 * 60),
+        ];
+        let options = DisplayOptions {
+            now: Some(now),
+            ..default_options()
+        };
+        let mut table = BranchTable::new(records, &options);
+        table.init();
+        let mut terminal = Terminal::new(TestBackend::new(70, 12)).unwrap();
+        draw(&mut terminal, &mut table);
+
+        let expected = [
+            "┌Recent branches─────────────────────────────────────────────────────┐",
+            "│     Name  Last Commit                                Author        │",
+            "│                                                                    │",
+            "│>>   main  11111111 (2 hours ago)                     Test          │",
+            "│           Work on main                                             │",
+            "│                                                                    │",
+            "│     topic 22222222 (3 days ago)                      Test          │",
+            "│           Work on topic                                            │",
+        ];
+        assert_eq!(screen_lines(&terminal)[..expected.len()], expected);
+    }
+
+    #[test]
+    fn an_empty_table_has_no_selection() {
+        let mut table = table_of(&[]);
+        assert_eq!(selected(&table), None);
+        assert!(!table.next());
+        assert!(!table.previous());
+    }
+}
@@ -24,7 +24,8 @@ SOFTWARE.
 Source: https://github.com/fdehau/tui-rs/blob/master/examples/util/event.rs
 */
 
-use std::io;
+use std::io::{self, Read};
+use std::os::unix::io::AsRawFd;
 use std::sync::mpsc;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -33,11 +34,12 @@ use std::sync::{
 use std::thread;
 use std::time::Duration;
 
-use termion::event::Key;
+use termion::event::{self as term_event, Key, MouseEvent};
 use termion::input::TermRead;
 
 pub enum Event<I> {
     Input(I),
+    Mouse(MouseEvent),
     Tick,
 }
 
@@ -48,13 +50,76 @@ pub struct Events {
     rx: mpsc::Receiver<Event<Key>>,
     input_handle: thread::JoinHandle<()>,
     ignore_exit_key: Arc<AtomicBool>,
+    // While set, keys are left for another program reading the terminal
+    paused: Arc<AtomicBool>,
+    stopped: Arc<AtomicBool>,
     tick_handle: thread::JoinHandle<()>,
 }
 
+// Waits up to `timeout` until there is something to read, so the thread never
+// blocks in a read it cannot be pulled out of
+fn wait_readable(fd: i32, timeout: Duration) -> bool {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.as_millis().min(i32::MAX as u128) as i32;
+    unsafe { libc::poll(&mut pollfd, 1, timeout) > 0 }
+}
+
+// Sequences longer than this are never held back waiting for the rest
+const MAX_SEQUENCE_LEN: usize = 32;
+
+// How many bytes at the end of `bytes` start a key or mouse sequence that is not
+// complete yet. Only a read that filled the buffer can have cut one short; anything
+// else, like a lone Esc, is a key press of its own.
+fn incomplete_tail(bytes: &[u8], filled: bool) -> usize {
+    if !filled {
+        return 0;
+    }
+    // A multi-byte UTF-8 character cut short
+    for back in 1..=3.min(bytes.len()) {
+        let byte = bytes[bytes.len() - back];
+        if byte & 0xc0 != 0x80 {
+            let len = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            if len > back {
+                return back;
+            }
+            break;
+        }
+    }
+    let start = match bytes.iter().rposition(|&b| b == 0x1b) {
+        Some(start) => start,
+        None => return 0,
+    };
+    let tail = &bytes[start..];
+    let complete = match tail {
+        [_] => false,
+        // X10 mouse reports end in three raw bytes
+        [_, b'[', b'M', rest @ ..] => rest.len() >= 3,
+        [_, b'[', rest @ ..] => rest.iter().any(|b| (0x40..=0x7e).contains(b)),
+        [_, b'O', rest @ ..] => !rest.is_empty(),
+        _ => true,
+    };
+    if complete || tail.len() > MAX_SEQUENCE_LEN {
+        0
+    } else {
+        tail.len()
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
     pub exit_key: Key,
     pub tick_rate: Duration,
+    // How often the input thread checks whether it was paused or stopped
+    pub poll_interval: Duration,
 }
 
 impl Default for Config {
@@ -62,51 +127,128 @@ impl Default for Config {
         Config {
             exit_key: Key::Char('q'),
             tick_rate: Duration::from_millis(250),
+            poll_interval: Duration::from_millis(50),
         }
     }
 }
 
 impl Events {
-    pub fn new() -> Events {
-        Events::with_config(Config::default())
-    }
-
     pub fn with_config(config: Config) -> Events {
         let (tx, rx) = mpsc::channel();
         let ignore_exit_key = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::new(AtomicBool::new(false));
         let input_handle = {
             let tx = tx.clone();
             let ignore_exit_key = ignore_exit_key.clone();
+            let paused = paused.clone();
+            let stopped = stopped.clone();
             thread::spawn(move || {
-                let stdin = io::stdin();
-                for key in stdin.keys().flatten() {
-                    if let Err(err) = tx.send(Event::Input(key)) {
-                        eprintln!("{err}");
-                        return;
+                // Read keys from the terminal itself so stdin stays free for piped input
+                let (mut input, fd): (Box<dyn io::Read>, i32) = match termion::get_tty() {
+                    Ok(tty) => {
+                        let fd = tty.as_raw_fd();
+                        (Box::new(tty), fd)
+                    }
+                    Err(_) => (Box::new(io::stdin()), io::stdin().as_raw_fd()),
+                };
+                let mut buf = [0; 64];
+                // Bytes of a sequence the last read cut short, parsed with the next one
+                let mut pending: Vec<u8> = vec![];
+                while !stopped.load(Ordering::Relaxed) {
+                    if paused.load(Ordering::Relaxed) {
+                        thread::sleep(config.poll_interval);
+                        continue;
+                    }
+                    if !wait_readable(fd, config.poll_interval) {
+                        continue;
                     }
-                    if !ignore_exit_key.load(Ordering::Relaxed) && key == config.exit_key {
-                        return;
+                    let n = match input.read(&mut buf) {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => n,
+                    };
+                    pending.extend_from_slice(&buf[..n]);
+                    let complete = pending.len() - incomplete_tail(&pending, n == buf.len());
+                    let bytes: Vec<u8> = pending.drain(..complete).collect();
+                    for event in bytes.events().flatten() {
+                        let event = match event {
+                            term_event::Event::Key(key) => Event::Input(key),
+                            term_event::Event::Mouse(mouse) => Event::Mouse(mouse),
+                            term_event::Event::Unsupported(_) => continue,
+                        };
+                        let exit = matches!(event, Event::Input(key) if key == config.exit_key);
+                        if tx.send(event).is_err() {
+                            return;
+                        }
+                        if exit && !ignore_exit_key.load(Ordering::Relaxed) {
+                            return;
+                        }
                     }
                 }
             })
         };
         let tick_handle = {
-            thread::spawn(move || loop {
-                if let Err(_e) = tx.send(Event::Tick) {
-                    //println!("error sending tick: {}", _e);
-                };
-                thread::sleep(config.tick_rate);
+            thread::spawn(move || {
+                // Stop once the receiver is gone rather than ticking forever
+                while tx.send(Event::Tick).is_ok() {
+                    thread::sleep(config.tick_rate);
+                }
             })
         };
         Events {
             rx,
             ignore_exit_key,
+            paused,
+            stopped,
             input_handle,
             tick_handle,
         }
     }
 
+    // Stops reading keys so a program run in the foreground gets all of them
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn disable_exit_key(&mut self) {
+        self.ignore_exit_key.store(true, Ordering::Relaxed);
+    }
+
     pub fn next(&self) -> Result<Event<Key>, mpsc::RecvError> {
         self.rx.recv()
     }
 }
+
+// Keys typed after the picker closes belong to whatever runs next, e.g. --peek
+impl Drop for Events {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequences_cut_short_by_a_full_read_are_held_back() {
+        assert_eq!(incomplete_tail(b"ab\x1b[1;", true), 4);
+        assert_eq!(incomplete_tail(b"ab\x1b[A", true), 0);
+        assert_eq!(incomplete_tail(b"ab\x1b[<0;12;3", true), 9);
+        assert_eq!(incomplete_tail(b"ab\x1b[<0;12;3M", true), 0);
+        assert_eq!(incomplete_tail(b"ab\x1b[M !", true), 5);
+        assert_eq!(incomplete_tail(b"ab\x1b", true), 1);
+        assert_eq!(incomplete_tail("ab\u{e9}".as_bytes(), true), 0);
+        assert_eq!(incomplete_tail(&"ab\u{e9}".as_bytes()[..3], true), 1);
+    }
+
+    #[test]
+    fn a_read_that_did_not_fill_the_buffer_is_parsed_whole() {
+        assert_eq!(incomplete_tail(b"\x1b", false), 0);
+        assert_eq!(incomplete_tail(b"ab\x1b[1;", false), 0);
+    }
+}
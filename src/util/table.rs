@@ -0,0 +1,272 @@
+use tui::{
+    buffer::{self, Buffer},
+    layout::{Constraint, Rect},
+    style::Style,
+    widgets::{Block, StatefulWidget, Text, Widget},
+};
+use unicode_width::UnicodeWidthStr;
+
+// A cell is a sequence of spans. Raw spans take the style of their row, styled ones
+// keep their own except on the highlighted row.
+pub type Cell = Vec<Text<'static>>;
+
+pub fn plain(s: impl Into<String>) -> Cell {
+    vec![Text::raw(s.into())]
+}
+
+// Terminal columns the cell's text takes up
+pub fn cell_width(cell: &[Text]) -> usize {
+    cell.iter()
+        .map(|text| match text {
+            Text::Raw(s) | Text::Styled(s, _) => s.width(),
+        })
+        .sum()
+}
+
+// Text the table drew as an OSC 8 hyperlink. The escape sequences can't go in the
+// buffer, where each cell holds what fills one terminal column, so they are written
+// out around the text once the frame is on screen.
+pub struct Link {
+    pub url: String,
+    pub y: u16,
+    // Cells the text was drawn in, leaving out those a wide character spills into
+    pub cells: Vec<(u16, buffer::Cell)>,
+}
+
+// Rendered after everything else, drops the links something has since been drawn over
+pub struct UncoveredLinks<'a>(pub &'a mut Vec<Link>);
+
+impl<'a> Widget for UncoveredLinks<'a> {
+    fn render(self, _area: Rect, buf: &mut Buffer) {
+        self.0.retain(|link| {
+            link.cells
+                .iter()
+                .all(|(x, cell)| buf.get(*x, link.y) == cell)
+        });
+    }
+}
+
+#[derive(Default)]
+pub struct TableState {
+    offset: usize,
+    selected: Option<usize>,
+    links: Vec<Link>,
+}
+
+impl TableState {
+    pub fn select(&mut self, index: Option<usize>) {
+        self.selected = index;
+        if index.is_none() {
+            self.offset = 0;
+        }
+    }
+
+    // The next render scrolls down from the top only as far as the selected row needs
+    pub fn scroll_to_top(&mut self) {
+        self.offset = 0;
+    }
+
+    // First row shown as of the last render
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // Links drawn by the last render
+    pub fn take_links(&mut self) -> Vec<Link> {
+        std::mem::take(&mut self.links)
+    }
+}
+
+// Same layout as tui's Table, which can only style whole rows
+pub struct Table<'a> {
+    block: Option<Block<'a>>,
+    header: &'a [String],
+    rows: &'a [Vec<Cell>],
+    widths: &'a [Constraint],
+    style: Style,
+    highlight_style: Style,
+    highlight_symbol: &'a str,
+    // Column whose text is made an OSC 8 hyperlink, with one optional URL per row
+    links: Option<(usize, &'a [Option<String>])>,
+}
+
+impl<'a> Table<'a> {
+    pub fn new(header: &'a [String], rows: &'a [Vec<Cell>]) -> Table<'a> {
+        Table {
+            block: None,
+            header,
+            rows,
+            widths: &[],
+            style: Style::default(),
+            highlight_style: Style::default(),
+            highlight_symbol: "",
+            links: None,
+        }
+    }
+
+    pub fn block(mut self, block: Block<'a>) -> Table<'a> {
+        self.block = Some(block);
+        self
+    }
+
+    pub fn widths(mut self, widths: &'a [Constraint]) -> Table<'a> {
+        self.widths = widths;
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Table<'a> {
+        self.style = style;
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Table<'a> {
+        self.highlight_style = style;
+        self
+    }
+
+    pub fn highlight_symbol(mut self, symbol: &'a str) -> Table<'a> {
+        self.highlight_symbol = symbol;
+        self
+    }
+
+    pub fn links(mut self, column: usize, links: &'a [Option<String>]) -> Table<'a> {
+        self.links = Some((column, links));
+        self
+    }
+
+    // Lengths are taken as is and percentages are of the whole area, with columns that
+    // do not fit anymore cut short
+    fn column_widths(&self, area: Rect, inner: Rect) -> Vec<u16> {
+        let mut remaining = inner.width;
+        self.widths
+            .iter()
+            .map(|constraint| {
+                let width = match *constraint {
+                    Constraint::Length(v) | Constraint::Min(v) | Constraint::Max(v) => v,
+                    Constraint::Percentage(p) => {
+                        (u32::from(area.width) * u32::from(p) / 100) as u16
+                    }
+                    Constraint::Ratio(n, d) => (u32::from(area.width) * n / d.max(1)) as u16,
+                }
+                .min(remaining);
+                remaining = remaining.saturating_sub(width + 1);
+                width
+            })
+            .collect()
+    }
+}
+
+// Spans on the highlighted row all take the highlight style. Text that does not fit
+// ends in "…". Returns where the text ends.
+fn draw_cell(buf: &mut Buffer, area: Rect, cell: &[Text], style: Style, highlighted: bool) -> u16 {
+    let overflows = cell_width(cell) > area.width as usize;
+    let right = if overflows {
+        area.right().saturating_sub(1)
+    } else {
+        area.right()
+    };
+    let mut x = area.left();
+    let mut last_style = style;
+    for text in cell {
+        if x >= right {
+            break;
+        }
+        let (s, span_style) = match text {
+            Text::Styled(s, span_style) if !highlighted => (s, *span_style),
+            Text::Raw(s) | Text::Styled(s, _) => (s, style),
+        };
+        let (next, _) = buf.set_stringn(x, area.top(), s, (right - x) as usize, span_style);
+        x = next;
+        last_style = span_style;
+    }
+    if overflows && area.width > 0 {
+        buf.set_string(x, area.top(), "…", last_style);
+        x += 1;
+    }
+    x
+}
+
+// The text drawn between `start` and `end`, to be linked to `url`
+fn link_text(buf: &Buffer, start: u16, end: u16, y: u16, url: &str) -> Link {
+    let mut cells = vec![];
+    let mut x = start;
+    while x < end {
+        let cell = buf.get(x, y);
+        cells.push((x, cell.clone()));
+        x += (cell.symbol.width() as u16).max(1);
+    }
+    Link {
+        url: url.to_string(),
+        y,
+        cells,
+    }
+}
+
+impl<'a> StatefulWidget for Table<'a> {
+    type State = TableState;
+
+    fn render(mut self, area: Rect, buf: &mut Buffer, state: &mut TableState) {
+        state.links.clear();
+        let table_area = match self.block {
+            Some(ref mut b) => {
+                b.render(area, buf);
+                b.inner(area)
+            }
+            None => area,
+        };
+        let widths = self.column_widths(area, table_area);
+
+        let mut y = table_area.top();
+        if y < table_area.bottom() {
+            let mut x = table_area.left();
+            for (w, title) in widths.iter().zip(self.header) {
+                buf.set_stringn(x, y, title, *w as usize, Style::default());
+                x += w + 1;
+            }
+        }
+        // One blank line between the header and the rows
+        y += 2;
+        if y >= table_area.bottom() {
+            return;
+        }
+
+        let remaining = (table_area.bottom() - y) as usize;
+        // Scroll just enough to keep the selected row in view
+        state.offset = match state.selected {
+            Some(selected) if selected >= state.offset + remaining => selected + 1 - remaining,
+            Some(selected) if selected < state.offset => selected,
+            Some(_) => state.offset,
+            None => 0,
+        };
+        let blank_symbol = " ".repeat(self.highlight_symbol.width());
+        let rows = self.rows.iter().enumerate().skip(state.offset);
+        for (i, (index, row)) in rows.take(remaining).enumerate() {
+            let row_y = y + i as u16;
+            let highlighted = state.selected == Some(index);
+            let (style, symbol) = if highlighted {
+                (self.highlight_style, self.highlight_symbol)
+            } else {
+                (self.style, blank_symbol.as_str())
+            };
+            let mut x = table_area.left();
+            for (c, (w, cell)) in widths.iter().zip(row).enumerate() {
+                let mut start = x;
+                if c == 0 {
+                    let (next, _) = buf.set_stringn(x, row_y, symbol, *w as usize, style);
+                    start = next;
+                }
+                let width = (x + w).saturating_sub(start);
+                let cell_area = Rect::new(start, row_y, width, 1);
+                let end = draw_cell(buf, cell_area, cell, style, highlighted);
+                let url = self
+                    .links
+                    .filter(|(column, _)| *column == c)
+                    .and_then(|(_, links)| links.get(index)?.as_deref());
+                if let Some(url) = url.filter(|_| end > start) {
+                    state.links.push(link_text(buf, start, end, row_y, url));
+                }
+                x += w + 1;
+            }
+        }
+    }
+}